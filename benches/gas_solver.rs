@@ -0,0 +1,185 @@
+#![allow(clippy::type_complexity)]
+use bevy::tasks::TaskPool;
+use bevy_space_game::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+//A single warm room in the middle of a cold grid
+fn room_grid<const N: usize>() -> (Box<[[GasMixture; N]; N]>, Box<[[bool; N]; N]>) {
+    let mut gas = new_boxed_grid::<_, N>(GasMixture {
+        temperature: 2.7,
+        ..Default::default()
+    });
+    let mut walls = new_boxed_grid::<_, N>(false);
+    let (min, max) = (N / 4, 3 * N / 4);
+    for i in min..=max {
+        for j in min..=max {
+            if i == min || i == max || j == min || j == max {
+                walls[i][j] = true;
+            } else {
+                gas[i][j] = GasMixture::single_gas(Gas(0), 83.0, 293.0);
+            }
+        }
+    }
+    (gas, walls)
+}
+
+//Air everywhere with a scattered percentage of tiles walled off
+fn scattered_grid<const N: usize>(
+    wall_percent: usize,
+) -> (Box<[[GasMixture; N]; N]>, Box<[[bool; N]; N]>) {
+    let gas = new_boxed_grid::<_, N>(GasMixture::single_gas(Gas(0), 83.0, 293.0));
+    let mut walls = new_boxed_grid::<_, N>(false);
+    for i in 0..N {
+        for j in 0..N {
+            //Cheap deterministic scatter so runs are comparable
+            walls[i][j] = (i * 7919 + j * 104729) % 100 < wall_percent;
+        }
+    }
+    (gas, walls)
+}
+
+fn thermal_grid<const N: usize>(walls: &[[bool; N]; N]) -> Box<[[Thermal; N]; N]> {
+    let mut thermal = new_boxed_grid::<_, N>(Thermal::GAS);
+    for i in 0..N {
+        for j in 0..N {
+            if walls[i][j] {
+                thermal[i][j] = WallMaterial::Metal.thermal();
+            }
+        }
+    }
+    thermal
+}
+
+fn bench_size<const N: usize>(c: &mut Criterion, pool: &TaskPool, gases: &GasRegistry) {
+    let mut group = c.benchmark_group("diffuse_step");
+    let (x0, walls) = room_grid::<N>();
+    let thermal = thermal_grid(&walls);
+    for solver in [GasSolver::GaussSeidel, GasSolver::RedBlack] {
+        group.bench_with_input(
+            BenchmarkId::new(format!("{:?}", solver), N),
+            &solver,
+            |b, solver| {
+                let mut x = new_boxed_grid::<_, N>(GasMixture::default());
+                b.iter(|| {
+                    diffuse_step(
+                        &x0,
+                        &mut x,
+                        &walls,
+                        &thermal,
+                        gases,
+                        1.0 / 60.0,
+                        *solver,
+                        pool,
+                    )
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_walls<const N: usize>(c: &mut Criterion, pool: &TaskPool, gases: &GasRegistry) {
+    let mut group = c.benchmark_group(format!("diffuse_step/{}", N));
+    for wall_percent in [0, 10, 30] {
+        let (x0, walls) = scattered_grid::<N>(wall_percent);
+        let thermal = thermal_grid(&walls);
+        group.bench_with_input(
+            BenchmarkId::new("walls_percent", wall_percent),
+            &wall_percent,
+            |b, _| {
+                let mut x = new_boxed_grid::<_, N>(GasMixture::default());
+                b.iter(|| {
+                    diffuse_step(
+                        &x0,
+                        &mut x,
+                        &walls,
+                        &thermal,
+                        gases,
+                        1.0 / 60.0,
+                        GasSolver::GaussSeidel,
+                        pool,
+                    )
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+//The whole gas frame as the game runs it: copying tiles in and out through the world,
+//relieving pressure, venting to space and colouring the overlay sprites
+fn gas_app(wall_percent: usize) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(comp_from_config!(GasRegistry, "config/gases.ron"))
+        .add_event::<OverPressureEvent>()
+        .add_system(diffuse_gas_grid);
+
+    let (gas, walls) = scattered_grid::<GRID_SIZE>(wall_percent);
+    //Floored everywhere but the edge so a little leaks to space each frame
+    let mut floors = new_boxed_grid::<_, GRID_SIZE>(true);
+    for k in 0..GRID_SIZE {
+        for (i, j) in [(k, 0), (k, GRID_SIZE - 1), (0, k), (GRID_SIZE - 1, k)] {
+            floors[i][j] = false;
+        }
+    }
+    let limits = comp_from_config!(PressureLimits);
+    let mut grid = GasGrid {
+        grid: [[Entity::from_raw(0); GRID_SIZE]; GRID_SIZE],
+        wall_mask: *walls,
+        thermal: *thermal_grid(&walls),
+        unsafe_mask: [[false; GRID_SIZE]; GRID_SIZE],
+        space_mask: *space_mask(&walls, &floors),
+        max_pressure: [[limits.max_pressure; GRID_SIZE]; GRID_SIZE],
+        tile_size: 32.0,
+    };
+    for i in 0..GRID_SIZE {
+        for j in 0..GRID_SIZE {
+            grid.grid[i][j] = app
+                .world
+                .spawn()
+                .insert(gas[i][j])
+                .insert(TextureAtlasSprite::default())
+                .id();
+        }
+    }
+    app.world
+        .spawn()
+        .insert(grid)
+        .insert(GasVisualizationSettings::Pressure)
+        .insert(GasSolver::GaussSeidel)
+        .insert(limits);
+    app
+}
+
+fn gas_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("diffuse_gas_grid/{}", GRID_SIZE));
+    for wall_percent in [0, 10, 30] {
+        let mut app = gas_app(wall_percent);
+        group.bench_with_input(
+            BenchmarkId::new("walls_percent", wall_percent),
+            &wall_percent,
+            |b, _| b.iter(|| app.update()),
+        );
+    }
+    group.finish();
+}
+
+fn grid_sizes(c: &mut Criterion) {
+    let pool = TaskPool::new();
+    let gases = comp_from_config!(GasRegistry, "config/gases.ron");
+    bench_walls::<25>(c, &pool, &gases);
+    bench_walls::<50>(c, &pool, &gases);
+    bench_walls::<100>(c, &pool, &gases);
+}
+
+fn solvers(c: &mut Criterion) {
+    let pool = TaskPool::new();
+    let gases = comp_from_config!(GasRegistry, "config/gases.ron");
+    bench_size::<50>(c, &pool, &gases);
+    bench_size::<100>(c, &pool, &gases);
+    bench_size::<200>(c, &pool, &gases);
+}
+
+criterion_group!(benches, solvers, grid_sizes, gas_frame);
+criterion_main!(benches);
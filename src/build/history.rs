@@ -0,0 +1,288 @@
+use crate::prelude::*;
+
+use super::HistoryPlugin;
+
+//Oldest edits are forgotten past this many steps
+const MAX_HISTORY: usize = 100;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EditHistory::default())
+            .add_system(undo_redo);
+    }
+}
+
+impl EditHistory {
+    /// Adds a step made of the edits that reverse it, a new step clears the redo stack
+    pub fn record(&mut self, undo: Vec<Edit>) {
+        if undo.is_empty() {
+            return;
+        }
+        self.undo.push(undo);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Reverses the newest step that still applies, apply makes a step and returns its reverse
+    pub fn undo_step(&mut self, apply: impl FnMut(Vec<Edit>) -> Vec<Edit>) {
+        move_step(&mut self.undo, &mut self.redo, apply);
+    }
+
+    /// Makes the newest undone step that still applies again
+    pub fn redo_step(&mut self, apply: impl FnMut(Vec<Edit>) -> Vec<Edit>) {
+        move_step(&mut self.redo, &mut self.undo, apply);
+    }
+}
+
+//Steps where every edit went stale, like a rotation of an entity a later undo despawned, are
+//dropped so each key press does something if anything is left to do
+fn move_step(
+    from: &mut Vec<Vec<Edit>>,
+    to: &mut Vec<Vec<Edit>>,
+    mut apply: impl FnMut(Vec<Edit>) -> Vec<Edit>,
+) {
+    while let Some(step) = from.pop() {
+        let reverse = apply(step);
+        if !reverse.is_empty() {
+            to.push(reverse);
+            return;
+        }
+    }
+}
+
+impl<'w, 's> StationEditor<'w, 's> {
+    /// Makes the edit and returns the one that reverses it, None if it no longer applies
+    pub fn apply(&mut self, edit: Edit) -> Option<Edit> {
+        if !self.stockpile.covers(edit.metal) {
+            info!("Not enough metal, {} needed", -edit.metal);
+            return None;
+        }
+        let (grid, _) = self.wall_query.single();
+        let gas_grid = self.gas_query.single();
+
+        //Walls only change in the walls stage, edits in one step never touch the same tile twice
+        let inverse = match edit.kind {
+            EditKind::AddWall { tile, .. } | EditKind::RemoveWall { tile, .. } => {
+                let tile_gas = *self.tile_query.get(gas_grid.grid[tile.0][tile.1]).ok()?;
+                let inverse = wall_inverse(edit, grid.walls[tile.0][tile.1].is_some(), tile_gas)?;
+                match edit.kind {
+                    EditKind::AddWall { tile, material } => {
+                        self.wall_events.send(WallEvent::Place { tile, material })
+                    }
+                    EditKind::RemoveWall { tile, gas, .. } => {
+                        self.wall_events.send(WallEvent::Remove { tile });
+                        if let Some(gas) = gas {
+                            self.reclaim_gas(tile, gas);
+                        }
+                    }
+                    _ => {}
+                }
+                inverse.kind
+            }
+            EditKind::AddObject {
+                buildable,
+                position,
+                facing,
+            } => {
+                let entity = match buildable {
+                    Buildable::Wall => return None,
+                    Buildable::Door => {
+                        let id = self
+                            .door_query
+                            .iter()
+                            .map(|door| door.id + 1)
+                            .max()
+                            .unwrap_or_default();
+                        spawn_door(&mut self.commands, position, facing, id)
+                    }
+                    Buildable::Canister => {
+                        spawn_canister(&mut self.commands, position, GasMixture::default())
+                    }
+                    Buildable::Terminal => spawn_terminal(&mut self.commands, position, facing),
+                    Buildable::Furnace => spawn_furnace(&mut self.commands, position, &self.assets),
+                    Buildable::Vent => spawn_vent(&mut self.commands, position),
+                };
+                EditKind::RemoveObject {
+                    entity,
+                    buildable,
+                    position,
+                    facing,
+                }
+            }
+            EditKind::RemoveObject {
+                entity,
+                buildable,
+                position,
+                facing,
+            } => {
+                //Deleted some other way since
+                self.exists_query.get(entity).ok()?;
+                self.commands.entity(entity).despawn_recursive();
+                EditKind::AddObject {
+                    buildable,
+                    position,
+                    facing,
+                }
+            }
+            EditKind::Rotate { entity, turns } => {
+                let (mut graphic, mut transform, pump, panel) =
+                    self.facing_query.get_mut(entity).ok()?;
+                let turn = |facing: Orientation| (0..turns).fold(facing, |f, _| f.rotated());
+                if let Graphic::WorldObject(WorldObject::Terminal(facing)) = *graphic {
+                    *graphic = Graphic::WorldObject(WorldObject::Terminal(turn(facing)));
+                }
+                if let Some(mut pump) = pump {
+                    pump.direction = turn(pump.direction);
+                    transform.rotation = Quat::from_rotation_z(pump.direction.angle());
+                }
+                if let Some(mut panel) = panel {
+                    panel.direction = turn(panel.direction);
+                }
+                EditKind::Rotate {
+                    entity,
+                    turns: (4 - turns % 4) % 4,
+                }
+            }
+        };
+
+        self.stockpile.metal += edit.metal;
+        Some(Edit {
+            kind: inverse,
+            metal: -edit.metal,
+        })
+    }
+
+    /// Edit that takes down entity with a refund, None unless the build palette can make it
+    pub fn removal(&self, entity: Entity) -> Option<Edit> {
+        let (transform, door, canister, terminal, furnace, vent) =
+            self.built_query.get(entity).ok()?;
+        let (graphic, ..) = self.facing_query.get(entity).ok()?;
+        let buildable = [
+            door.map(|_| Buildable::Door),
+            canister.map(|_| Buildable::Canister),
+            terminal.map(|_| Buildable::Terminal),
+            furnace.map(|_| Buildable::Furnace),
+            vent.map(|_| Buildable::Vent),
+        ]
+        .into_iter()
+        .flatten()
+        .next()?;
+        let facing = match graphic {
+            Graphic::WorldObject(WorldObject::Terminal(facing)) => *facing,
+            _ => Orientation::from_rotation(transform.rotation),
+        };
+        Some(Edit {
+            kind: EditKind::RemoveObject {
+                entity,
+                buildable,
+                position: transform.translation,
+                facing,
+            },
+            metal: self.costs.cost(buildable) * self.costs.refund,
+        })
+    }
+
+    /// Edit that turns entity a quarter turn clockwise, None unless it has a facing
+    pub fn rotation(&self, entity: Entity) -> Option<Edit> {
+        let (graphic, _, pump, panel) = self.facing_query.get(entity).ok()?;
+        let terminal = matches!(graphic, Graphic::WorldObject(WorldObject::Terminal(_)));
+        (terminal || pump.is_some() || panel.is_some()).then_some(Edit {
+            kind: EditKind::Rotate { entity, turns: 1 },
+            metal: 0.0,
+        })
+    }
+
+    //Placing the wall pushed this gas into the neighbours, take back what is still there
+    //rather than make new moles
+    fn reclaim_gas(&mut self, tile: (usize, usize), gas: GasMixture) {
+        let gas_grid = self.gas_query.single();
+        let mut tiles = gas_grid.mixtures(&self.tile_query);
+        let edited = unwall_gas(&mut tiles, &gas_grid.wall_mask, tile, &gas, &self.gases);
+        gas_grid.write_mixtures(&tiles, &edited, &mut self.tile_query);
+    }
+
+    /// Applies a recorded step newest edit first, returning the step that reverses it
+    pub fn apply_step(&mut self, step: Vec<Edit>) -> Vec<Edit> {
+        step.into_iter()
+            .rev()
+            .filter_map(|edit| self.apply(edit))
+            .collect()
+    }
+}
+
+/// Reverse of a wall edit, None if the tile already is the way the edit leaves it. The gas on
+/// the tile is kept so taking the wall down again puts it back
+pub fn wall_inverse(edit: Edit, walled: bool, tile_gas: GasMixture) -> Option<Edit> {
+    let kind = match edit.kind {
+        EditKind::AddWall { tile, material } if !walled => EditKind::RemoveWall {
+            tile,
+            material,
+            gas: Some(tile_gas),
+        },
+        EditKind::RemoveWall { tile, material, .. } if walled => {
+            EditKind::AddWall { tile, material }
+        }
+        _ => return None,
+    };
+    Some(Edit {
+        kind,
+        metal: -edit.metal,
+    })
+}
+
+/// Makes a wall edit on plain grids, moving gas with the same functions the walls and gas
+/// stages use in game. Returns the edit that reverses it, None if it does not apply or the
+/// metal is short
+pub fn edit_walls<const N: usize>(
+    edit: Edit,
+    walls: &mut [[bool; N]; N],
+    tiles: &mut [[GasMixture; N]; N],
+    stockpile: &mut Stockpile,
+    gases: &GasRegistry,
+) -> Option<Edit> {
+    let tile = match edit.kind {
+        EditKind::AddWall { tile, .. } | EditKind::RemoveWall { tile, .. } => tile,
+        _ => return None,
+    };
+    if !stockpile.covers(edit.metal) {
+        return None;
+    }
+    let inverse = wall_inverse(edit, walls[tile.0][tile.1], tiles[tile.0][tile.1])?;
+
+    match edit.kind {
+        EditKind::AddWall { .. } => {
+            walls[tile.0][tile.1] = true;
+            wall_over_gas(tiles, walls, &[tile], gases);
+        }
+        EditKind::RemoveWall { gas, .. } => {
+            if let Some(gas) = gas {
+                unwall_gas(tiles, walls, tile, &gas, gases);
+            }
+            walls[tile.0][tile.1] = false;
+        }
+        _ => {}
+    }
+    stockpile.metal += edit.metal;
+    Some(inverse)
+}
+
+//Ctrl+Z undoes the last step, Ctrl+Y redoes it
+fn undo_redo(
+    mut editor: StationEditor,
+    mut history: ResMut<EditHistory>,
+    keyboard: Res<Input<KeyCode>>,
+    console: Res<Console>,
+) {
+    let ctrl = keyboard.pressed(KeyCode::LControl) || keyboard.pressed(KeyCode::RControl);
+    if console.is_open() || !ctrl {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Z) {
+        history.undo_step(|step| editor.apply_step(step));
+    }
+    if keyboard.just_pressed(KeyCode::Y) {
+        history.redo_step(|step| editor.apply_step(step));
+    }
+}
@@ -0,0 +1,220 @@
+use bevy::{app::PluginGroupBuilder, ecs::system::SystemParam};
+
+use crate::prelude::*;
+
+mod history;
+mod placement;
+mod selection;
+
+pub use history::{edit_walls, wall_inverse};
+pub use placement::placement_error;
+
+/// Things the build palette can place, in palette order
+#[derive(Inspectable, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Buildable {
+    #[default]
+    Wall,
+    Door,
+    Canister,
+    Terminal,
+    Furnace,
+    Vent,
+}
+
+impl Buildable {
+    pub const PALETTE: [Buildable; 6] = [
+        Buildable::Wall,
+        Buildable::Door,
+        Buildable::Canister,
+        Buildable::Terminal,
+        Buildable::Furnace,
+        Buildable::Vent,
+    ];
+}
+
+/// How a wall drag from press to release is filled in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum WallShape {
+    #[default]
+    Line,
+    Rectangle,
+    FilledRectangle,
+}
+
+impl WallShape {
+    pub fn next(self) -> Self {
+        match self {
+            WallShape::Line => WallShape::Rectangle,
+            WallShape::Rectangle => WallShape::FilledRectangle,
+            WallShape::FilledRectangle => WallShape::Line,
+        }
+    }
+
+    pub fn tiles(self, start: (usize, usize), end: (usize, usize)) -> Vec<(usize, usize)> {
+        match self {
+            WallShape::Line => line_tiles(start, end),
+            WallShape::Rectangle => rectangle_tiles(start, end, false),
+            WallShape::FilledRectangle => rectangle_tiles(start, end, true),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BuildTool {
+    Place(Buildable),
+    Deconstruct,
+}
+
+/// Resource: Clicks place or remove things instead of selecting while active
+pub struct BuildMode {
+    pub active: bool,
+    pub tool: BuildTool,
+    pub facing: Orientation,
+    pub wall_shape: WallShape,
+    //Tile the wall drag started on, walls go down when it is released
+    pub drag_start: Option<(usize, usize)>,
+}
+
+impl Default for BuildMode {
+    fn default() -> Self {
+        Self {
+            active: false,
+            tool: BuildTool::Place(Buildable::Wall),
+            facing: Orientation::Right,
+            wall_shape: WallShape::default(),
+            drag_start: None,
+        }
+    }
+}
+
+/// Resource: Metal each buildable takes, deconstructing gives back refund of it
+#[derive(Deserialize, Clone)]
+pub struct BuildCosts {
+    pub costs: HashMap<Buildable, f64>,
+    pub refund: f64,
+}
+
+impl BuildCosts {
+    pub fn cost(&self, buildable: Buildable) -> f64 {
+        self.costs.get(&buildable).copied().unwrap_or_default()
+    }
+}
+
+/// Resource: Materials on hand for building
+#[derive(Deserialize, Clone, Copy)]
+pub struct Stockpile {
+    pub metal: f64,
+}
+
+impl Stockpile {
+    /// Takes cost out if there is enough
+    pub fn spend(&mut self, cost: f64) -> bool {
+        if self.metal < cost {
+            return false;
+        }
+        self.metal -= cost;
+        true
+    }
+
+    /// True if metal can be added, negative for a cost, without going below zero
+    pub fn covers(&self, metal: f64) -> bool {
+        self.metal + metal >= 0.0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum EditKind {
+    AddWall {
+        tile: (usize, usize),
+        material: WallMaterial,
+    },
+    //Gas is what the tile held before the wall went in, put back when there is some
+    RemoveWall {
+        tile: (usize, usize),
+        material: WallMaterial,
+        gas: Option<GasMixture>,
+    },
+    AddObject {
+        buildable: Buildable,
+        position: Vec3,
+        facing: Orientation,
+    },
+    RemoveObject {
+        entity: Entity,
+        buildable: Buildable,
+        position: Vec3,
+        facing: Orientation,
+    },
+    //Quarter turns clockwise
+    Rotate {
+        entity: Entity,
+        turns: usize,
+    },
+}
+
+/// One reversible change to the station, metal is added to the Stockpile when it is made
+#[derive(Clone, Copy)]
+pub struct Edit {
+    pub kind: EditKind,
+    pub metal: f64,
+}
+
+/// Resource: Groups of edits that reverse what was done, newest last
+#[derive(Default)]
+pub struct EditHistory {
+    pub undo: Vec<Vec<Edit>>,
+    pub redo: Vec<Vec<Edit>>,
+}
+
+/// Everything needed to make an Edit, shared by building and undo/redo
+#[derive(SystemParam)]
+pub struct StationEditor<'w, 's> {
+    commands: Commands<'w, 's>,
+    wall_query: Query<'w, 's, (&'static WallGrid, &'static GlobalTransform)>,
+    wall_events: EventWriter<'w, 's, WallEvent>,
+    gas_query: Query<'w, 's, &'static GasGrid>,
+    tile_query: Query<'w, 's, &'static mut GasMixture>,
+    door_query: Query<'w, 's, &'static Door>,
+    exists_query: Query<'w, 's, Entity>,
+    facing_query: Query<
+        'w,
+        's,
+        (
+            &'static mut Graphic,
+            &'static mut Transform,
+            Option<&'static mut Pump>,
+            Option<&'static mut SolarPanel>,
+        ),
+    >,
+    built_query: Query<
+        'w,
+        's,
+        (
+            &'static GlobalTransform,
+            Option<&'static Door>,
+            Option<&'static Canister>,
+            Option<&'static Terminal>,
+            Option<&'static Furnace>,
+            Option<&'static Vent>,
+        ),
+    >,
+    costs: Res<'w, BuildCosts>,
+    gases: Res<'w, GasRegistry>,
+    stockpile: ResMut<'w, Stockpile>,
+    assets: Res<'w, AssetServer>,
+}
+
+struct PlacementPlugin;
+struct HistoryPlugin;
+struct SelectionEditPlugin;
+
+pub struct BuildPluginGroup;
+
+impl PluginGroup for BuildPluginGroup {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(PlacementPlugin)
+            .add(HistoryPlugin)
+            .add(SelectionEditPlugin);
+    }
+}
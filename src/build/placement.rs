@@ -0,0 +1,470 @@
+use crate::{mouse::MousePosition, prelude::*};
+
+use super::PlacementPlugin;
+
+//Built objects sit above walls, the ghost above everything but the console
+const OBJECT_Z: f32 = 150.0;
+const GHOST_Z: f32 = 940.0;
+
+const PALETTE_KEYS: [KeyCode; 6] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+];
+
+#[derive(Component)]
+struct BuildGhost;
+
+#[derive(Component)]
+struct BuildText;
+
+//One per tile of the wall drag being previewed
+#[derive(Component)]
+struct WallPreview {
+    valid: bool,
+}
+
+impl Plugin for PlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BuildMode::default())
+            .insert_resource(comp_from_config!(BuildCosts))
+            .insert_resource(comp_from_config!(Stockpile))
+            .add_startup_system(spawn_ghost)
+            .add_startup_system(spawn_build_text)
+            .add_system(build_input)
+            .add_system(build_click.after(build_input))
+            .add_system(update_build_text)
+            .add_system(update_ghost)
+            .add_system(update_wall_preview)
+            .add_system(tint_wall_preview);
+    }
+}
+
+/// Why nothing can be built on tile, None if it is free and affordable
+pub fn placement_error(
+    tile: (usize, usize),
+    walls: &WallGrid,
+    occupied: &[(usize, usize)],
+    cost: f64,
+    stockpile: &Stockpile,
+) -> Option<&'static str> {
+    if walls.walls[tile.0][tile.1].is_some() {
+        Some("Tile has a wall")
+    } else if occupied.contains(&tile) {
+        Some("Tile is taken")
+    } else if stockpile.metal < cost {
+        Some("Not enough metal")
+    } else {
+        None
+    }
+}
+
+fn spawn_ghost(mut commands: Commands) {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Wall(
+            WallMaterial::Metal,
+            WallConnection::None,
+        )))
+        .insert(Transform::from_xyz(0.0, 0.0, GHOST_Z))
+        .insert(TileOrientation::default())
+        .insert(BuildGhost)
+        .insert(Name::new("BuildGhost"));
+}
+
+fn spawn_build_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(15.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("QuattrocentoSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(BuildText)
+        .insert(Name::new("BuildText"));
+}
+
+//B toggles build mode, number keys pick from the palette, X deconstructs, R turns and T changes wall shape
+fn build_input(mut build: ResMut<BuildMode>, keyboard: Res<Input<KeyCode>>, console: Res<Console>) {
+    if console.is_open() {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::B) {
+        build.active = !build.active;
+    }
+    if !build.active {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Escape) {
+        build.active = false;
+    }
+    for (key, buildable) in PALETTE_KEYS.iter().zip(Buildable::PALETTE) {
+        if keyboard.just_pressed(*key) {
+            build.tool = BuildTool::Place(buildable);
+        }
+    }
+    if keyboard.just_pressed(KeyCode::X) {
+        build.tool = BuildTool::Deconstruct;
+    }
+    if keyboard.just_pressed(KeyCode::R) {
+        build.facing = build.facing.rotated();
+    }
+    if keyboard.just_pressed(KeyCode::T) {
+        build.wall_shape = build.wall_shape.next();
+    }
+    if build.tool != BuildTool::Place(Buildable::Wall) {
+        build.drag_start = None;
+    }
+}
+
+fn occupied_tiles(
+    object_query: &Query<&GlobalTransform, With<Clickable>>,
+    grid_transform: &GlobalTransform,
+    tile_size: f32,
+) -> Vec<(usize, usize)> {
+    object_query
+        .iter()
+        .filter_map(|transform| {
+            world_to_tile(
+                transform.translation.truncate(),
+                grid_transform.translation.truncate(),
+                tile_size,
+            )
+        })
+        .collect()
+}
+
+//Walls go down along the drag shape on release, deconstruction follows a held button
+//and everything else goes down once per click, each of those is one undo step
+fn build_click(
+    mut editor: StationEditor,
+    mut history: ResMut<EditHistory>,
+    mut build: ResMut<BuildMode>,
+    object_query: Query<&GlobalTransform, With<Clickable>>,
+    material_query: Query<&Wall>,
+    costs: Res<BuildCosts>,
+    hovered: Res<HoveredEntity>,
+    mouse: Res<MousePosition>,
+    buttons: Res<Input<MouseButton>>,
+    mut removed: Local<Vec<Edit>>,
+) {
+    let held = buttons.pressed(MouseButton::Left);
+    let clicked = buttons.just_pressed(MouseButton::Left);
+    let released = buttons.just_released(MouseButton::Left);
+    if !held && !removed.is_empty() {
+        history.record(std::mem::take(&mut *removed));
+    }
+    if !build.active {
+        return;
+    }
+    let (grid, grid_transform) = editor.wall_query.single();
+    let grid_transform = *grid_transform;
+    let tile_size = grid.tile_size;
+    let tile = world_to_tile(mouse.0, grid_transform.translation.truncate(), tile_size);
+    //Letting go off the grid drops the drag
+    let drag_start = if released {
+        build.drag_start.take()
+    } else {
+        None
+    };
+    let tile = match tile {
+        Some(tile) => tile,
+        None => return,
+    };
+    let occupied = occupied_tiles(&object_query, &grid_transform, tile_size);
+
+    if let Some(start) = drag_start {
+        let cost = costs.cost(Buildable::Wall);
+        let mut undo = Vec::new();
+        for (x, y) in build.wall_shape.tiles(start, tile) {
+            let (grid, _) = editor.wall_query.single();
+            if placement_error((x, y), grid, &occupied, cost, &editor.stockpile).is_some() {
+                continue;
+            }
+            let edit = Edit {
+                kind: EditKind::AddWall {
+                    tile: (x, y),
+                    material: WallMaterial::default(),
+                },
+                metal: -cost,
+            };
+            undo.extend(editor.apply(edit));
+        }
+        history.record(undo);
+        return;
+    }
+
+    let buildable = match build.tool {
+        BuildTool::Place(buildable) => buildable,
+        BuildTool::Deconstruct => {
+            if !held {
+                return;
+            }
+            //Objects in front of a wall go first. Only what the palette can build can be taken
+            //down, anything else hovered (the player, other machines, labels) leaves the wall
+            if let Some(edit) = hovered.0.and_then(|ent| editor.removal(ent)) {
+                removed.extend(editor.apply(edit));
+            } else if let Some(wall) = grid.walls[tile.0][tile.1] {
+                let edit = Edit {
+                    kind: EditKind::RemoveWall {
+                        tile,
+                        material: material_query
+                            .get(wall)
+                            .map(|wall| wall.material)
+                            .unwrap_or_default(),
+                        gas: None,
+                    },
+                    metal: costs.cost(Buildable::Wall) * costs.refund,
+                };
+                removed.extend(editor.apply(edit));
+            }
+            return;
+        }
+    };
+
+    if !clicked {
+        return;
+    }
+    if buildable == Buildable::Wall {
+        build.drag_start = Some(tile);
+        return;
+    }
+    let cost = costs.cost(buildable);
+    if placement_error(tile, grid, &occupied, cost, &editor.stockpile).is_some() {
+        return;
+    }
+    let position = (grid_transform.translation.truncate()
+        + Vec2::new(tile.0 as f32, tile.1 as f32) * tile_size)
+        .extend(OBJECT_Z);
+    let edit = Edit {
+        kind: EditKind::AddObject {
+            buildable,
+            position,
+            facing: build.facing,
+        },
+        metal: -cost,
+    };
+    if let Some(undo) = editor.apply(edit) {
+        history.record(vec![undo]);
+    }
+}
+
+//Translucent preview on the tile under the mouse, red where it can not go
+fn update_ghost(
+    build: Res<BuildMode>,
+    grid_query: Query<(&WallGrid, &GlobalTransform)>,
+    object_query: Query<&GlobalTransform, With<Clickable>>,
+    mut ghost_query: Query<
+        (
+            &mut Graphic,
+            &mut Transform,
+            &mut Visibility,
+            &mut TextureAtlasSprite,
+            &mut TileOrientation,
+        ),
+        With<BuildGhost>,
+    >,
+    stockpile: Res<Stockpile>,
+    costs: Res<BuildCosts>,
+    rules: Res<AutotileRules>,
+    mouse: Res<MousePosition>,
+) {
+    let (mut graphic, mut transform, mut visibility, mut sprite, mut orientation) =
+        match ghost_query.get_single_mut() {
+            Ok(ghost) => ghost,
+            //Sprite is added the frame after the ghost spawns
+            Err(_) => return,
+        };
+    let (grid, grid_transform) = grid_query.single();
+    let tile = world_to_tile(
+        mouse.0,
+        grid_transform.translation.truncate(),
+        grid.tile_size,
+    );
+    let tile = match tile {
+        Some(tile) if build.active && build.drag_start.is_none() => tile,
+        _ => {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+            return;
+        }
+    };
+    visibility.is_visible = true;
+
+    let mut new_orientation = TileOrientation::default();
+    let (new_graphic, rotation) = match build.tool {
+        BuildTool::Place(Buildable::Wall) => {
+            let rule = autotile(&rules, &grid.walls, tile.0, tile.1);
+            new_orientation = rule.orientation;
+            (
+                WorldObject::Wall(WallMaterial::default(), rule.connection),
+                Quat::IDENTITY,
+            )
+        }
+        BuildTool::Place(Buildable::Door) => (
+            WorldObject::Door(false),
+            Quat::from_rotation_z(build.facing.angle()),
+        ),
+        BuildTool::Place(Buildable::Canister) => (WorldObject::Canister, Quat::IDENTITY),
+        BuildTool::Place(Buildable::Terminal) => {
+            (WorldObject::Terminal(build.facing), Quat::IDENTITY)
+        }
+        BuildTool::Place(Buildable::Furnace) => (WorldObject::Furance(false), Quat::IDENTITY),
+        BuildTool::Place(Buildable::Vent) => (WorldObject::Vent, Quat::IDENTITY),
+        BuildTool::Deconstruct => (
+            WorldObject::Wall(WallMaterial::default(), WallConnection::All),
+            Quat::IDENTITY,
+        ),
+    };
+    let new_graphic = Graphic::WorldObject(new_graphic);
+    if *graphic != new_graphic {
+        *graphic = new_graphic;
+    }
+    if *orientation != new_orientation {
+        *orientation = new_orientation;
+    }
+    transform.translation = (grid_transform.translation.truncate()
+        + Vec2::new(tile.0 as f32, tile.1 as f32) * grid.tile_size)
+        .extend(GHOST_Z);
+    transform.rotation = rotation;
+
+    sprite.color = match build.tool {
+        BuildTool::Place(buildable) => {
+            let occupied = occupied_tiles(&object_query, grid_transform, grid.tile_size);
+            let error = placement_error(tile, grid, &occupied, costs.cost(buildable), &stockpile);
+            if error.is_some() {
+                Color::rgba(1.0, 0.2, 0.2, 0.5)
+            } else {
+                Color::rgba(1.0, 1.0, 1.0, 0.5)
+            }
+        }
+        BuildTool::Deconstruct => Color::rgba(1.0, 0.2, 0.2, 0.3),
+    };
+}
+
+//Rebuilt whenever the dragged shape covers different tiles
+fn update_wall_preview(
+    mut commands: Commands,
+    build: Res<BuildMode>,
+    grid_query: Query<(&WallGrid, &GlobalTransform)>,
+    object_query: Query<&GlobalTransform, With<Clickable>>,
+    preview_query: Query<Entity, With<WallPreview>>,
+    stockpile: Res<Stockpile>,
+    costs: Res<BuildCosts>,
+    rules: Res<AutotileRules>,
+    mouse: Res<MousePosition>,
+    mut shown: Local<Vec<(usize, usize)>>,
+) {
+    let (grid, grid_transform) = grid_query.single();
+    let end = world_to_tile(
+        mouse.0,
+        grid_transform.translation.truncate(),
+        grid.tile_size,
+    );
+    let tiles = match (build.active, build.drag_start, end) {
+        (true, Some(start), Some(end)) => build.wall_shape.tiles(start, end),
+        _ => Vec::new(),
+    };
+    if *shown == tiles {
+        return;
+    }
+    for preview in preview_query.iter() {
+        commands.entity(preview).despawn();
+    }
+
+    //Connect the preview walls to each other as well as to what is already built
+    let mut walls = grid.walls;
+    for (x, y) in tiles.iter() {
+        walls[*x][*y].get_or_insert(Entity::from_raw(u32::MAX));
+    }
+    let occupied = occupied_tiles(&object_query, grid_transform, grid.tile_size);
+    let cost = costs.cost(Buildable::Wall);
+    let mut metal = stockpile.metal;
+    for (x, y) in tiles.iter() {
+        let valid =
+            placement_error((*x, *y), grid, &occupied, cost, &Stockpile { metal }).is_none();
+        if valid {
+            metal -= cost;
+        }
+        let rule = autotile(&rules, &walls, *x, *y);
+        let position = grid_transform.translation.truncate()
+            + Vec2::new(*x as f32, *y as f32) * grid.tile_size;
+        commands
+            .spawn()
+            .insert(Graphic::WorldObject(WorldObject::Wall(
+                WallMaterial::default(),
+                rule.connection,
+            )))
+            .insert(rule.orientation)
+            .insert(Transform::from_translation(position.extend(GHOST_Z)))
+            .insert(WallPreview { valid })
+            .insert(Name::new("WallPreview"));
+    }
+    *shown = tiles;
+}
+
+fn tint_wall_preview(mut preview_query: Query<(&WallPreview, &mut TextureAtlasSprite)>) {
+    for (preview, mut sprite) in preview_query.iter_mut() {
+        sprite.color = if preview.valid {
+            Color::rgba(1.0, 1.0, 1.0, 0.5)
+        } else {
+            Color::rgba(1.0, 0.2, 0.2, 0.5)
+        };
+    }
+}
+
+fn update_build_text(
+    build: Res<BuildMode>,
+    stockpile: Res<Stockpile>,
+    costs: Res<BuildCosts>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<BuildText>>,
+) {
+    let (mut text, mut visibility) = text_query.single_mut();
+    if visibility.is_visible != build.active {
+        visibility.is_visible = build.active;
+    }
+    if !build.active {
+        return;
+    }
+    let tool = match build.tool {
+        BuildTool::Place(Buildable::Wall) => format!(
+            "Wall {:?} ({:.0} metal each)",
+            build.wall_shape,
+            costs.cost(Buildable::Wall)
+        ),
+        BuildTool::Place(buildable) => format!(
+            "{:?} {:?} ({:.0} metal)",
+            buildable,
+            build.facing,
+            costs.cost(buildable)
+        ),
+        BuildTool::Deconstruct => "Deconstruct".to_string(),
+    };
+    let status = format!(
+        "BUILD: {}  Metal: {:.0}\n1-6 palette  X remove  R rotate  T wall shape  B exit",
+        tool, stockpile.metal
+    );
+    if text.sections[0].value != status {
+        text.sections[0].value = status;
+    }
+}
@@ -0,0 +1,60 @@
+use crate::prelude::*;
+
+use super::SelectionEditPlugin;
+
+impl Plugin for SelectionEditPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            delete_selection
+                .label("delete_selection")
+                .after("box_select"),
+        )
+        .add_system(rotate_selection.after("box_select"));
+    }
+}
+
+//Takes down the selected objects the build palette can make, as one undo step with refunds
+fn delete_selection(
+    mut editor: StationEditor,
+    mut history: ResMut<EditHistory>,
+    mut selection: ResMut<Selection>,
+    keyboard: Res<Input<KeyCode>>,
+    console: Res<Console>,
+) {
+    if console.is_open() || !keyboard.just_pressed(KeyCode::Delete) {
+        return;
+    }
+    let mut undo = Vec::new();
+    let mut kept = Vec::new();
+    for ent in selection.entities.drain(..) {
+        match editor.removal(ent) {
+            Some(edit) => undo.extend(editor.apply(edit)),
+            //The player and other machines stay selected
+            None => kept.push(ent),
+        }
+    }
+    selection.entities = kept;
+    history.record(undo);
+}
+
+//Turns anything with a facing a quarter turn clockwise, as one undo step
+fn rotate_selection(
+    mut editor: StationEditor,
+    mut history: ResMut<EditHistory>,
+    selection: Res<Selection>,
+    keyboard: Res<Input<KeyCode>>,
+    console: Res<Console>,
+    build: Res<BuildMode>,
+) {
+    //R turns the build preview instead in build mode
+    if console.is_open() || build.active || !keyboard.just_pressed(KeyCode::R) {
+        return;
+    }
+    let mut undo = Vec::new();
+    for ent in selection.entities.iter() {
+        if let Some(edit) = editor.rotation(*ent) {
+            undo.extend(editor.apply(edit));
+        }
+    }
+    history.record(undo);
+}
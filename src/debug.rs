@@ -1,5 +1,13 @@
 use bevy::prelude::*;
-use bevy_inspector_egui::{WorldInspectorParams, WorldInspectorPlugin};
+use bevy_inspector_egui::{
+    plugin::InspectorWindows, widgets::InspectorQuery, InspectorPlugin, WorldInspectorParams,
+    WorldInspectorPlugin,
+};
+
+use crate::graphics::Selected;
+use crate::machines::Console;
+
+type SelectionInspector = InspectorQuery<Entity, With<Selected>>;
 
 pub struct DebugPlugin;
 
@@ -10,6 +18,8 @@ impl Plugin for DebugPlugin {
             ..Default::default()
         })
         .add_plugin(WorldInspectorPlugin::new())
+        .add_plugin(InspectorPlugin::<SelectionInspector>::new())
+        .add_startup_system(hide_selection_inspector)
         .add_system(toggle_inspector);
     }
 }
@@ -17,8 +27,25 @@ impl Plugin for DebugPlugin {
 fn toggle_inspector(
     input: ResMut<Input<KeyCode>>,
     mut window_params: ResMut<WorldInspectorParams>,
+    mut inspector_windows: ResMut<InspectorWindows>,
+    console: Res<Console>,
 ) {
+    //Keys typed into the console are not shortcuts
+    if console.is_open() {
+        return;
+    }
     if input.just_pressed(KeyCode::Grave) {
         window_params.enabled = !window_params.enabled
     }
+    //I inspects whatever is selected
+    if input.just_pressed(KeyCode::I) {
+        let window = inspector_windows.window_data_mut::<SelectionInspector>();
+        window.visible = !window.visible;
+    }
+}
+
+fn hide_selection_inspector(mut inspector_windows: ResMut<InspectorWindows>) {
+    inspector_windows
+        .window_data_mut::<SelectionInspector>()
+        .visible = false;
 }
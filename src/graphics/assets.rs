@@ -35,14 +35,51 @@ impl Plugin for GameAssetsPlugin {
     }
 }
 
+impl AutotileRules {
+    /// Neighbours on the 4 sides, the corners only matter for blob tiles
+    pub const SIDES: u8 = 0b0101_0101;
+
+    /// Rule for an exact mask, then for just its sides, then the unconnected sprite
+    pub fn rule(&self, mask: u8) -> AutotileRule {
+        Self::lookup(&self.autotile, mask)
+    }
+
+    /// Same as rule but from the floor table
+    pub fn floor_rule(&self, mask: u8) -> AutotileRule {
+        Self::lookup(&self.floor_autotile, mask)
+    }
+
+    fn lookup(table: &HashMap<u8, AutotileRule>, mask: u8) -> AutotileRule {
+        table
+            .get(&mask)
+            .or_else(|| table.get(&(mask & Self::SIDES)))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Graphics {
+    /// Colour graphics_desc.ron gives the graphic, what it is spawned with before any tint
+    pub fn sprite_color(&self, graphic: &Graphic) -> Option<Color> {
+        self.graphics_map.get(graphic).map(|(desc, _)| desc.color)
+    }
+}
+
 //XXX Does not work if changed to graphic on another sheet
+//Colour is only set on spawn so tints from other systems stay
 fn update_sprite(
-    mut update_query: Query<(&mut TextureAtlasSprite, &Graphic), Changed<Graphic>>,
+    mut update_query: Query<
+        (&mut TextureAtlasSprite, &Graphic, Option<&TileOrientation>),
+        Or<(Changed<Graphic>, Changed<TileOrientation>)>,
+    >,
     graphics: Res<Graphics>,
 ) {
-    for (mut sprite, graphic) in update_query.iter_mut() {
-        if let Some((_, index)) = graphics.graphics_map.get(graphic) {
+    for (mut sprite, graphic, orientation) in update_query.iter_mut() {
+        if let Some((desc, index)) = graphics.graphics_map.get(graphic) {
+            let orientation = orientation.copied().unwrap_or_default();
             sprite.index = *index;
+            sprite.flip_x = desc.flip_x != orientation.flip_x;
+            sprite.flip_y = desc.flip_y != orientation.flip_y;
         } else {
             error!(
                 "Failed to load sprite for {:?}, missing in graphics_desc.ron?",
@@ -55,13 +92,23 @@ fn update_sprite(
 fn spawn_sprite(
     mut commands: Commands,
     res: Res<Graphics>,
-    graphics_to_spawn: Query<(Entity, &Graphic, Option<&Transform>), Without<TextureAtlasSprite>>,
+    graphics_to_spawn: Query<
+        (
+            Entity,
+            &Graphic,
+            Option<&Transform>,
+            Option<&TileOrientation>,
+        ),
+        Without<TextureAtlasSprite>,
+    >,
 ) {
-    for (ent, to_spawn, transform) in graphics_to_spawn.iter() {
+    for (ent, to_spawn, transform, orientation) in graphics_to_spawn.iter() {
         if let Some((desc, index)) = res.graphics_map.get(to_spawn) {
+            let orientation = orientation.copied().unwrap_or_default();
             let mut sprite = TextureAtlasSprite::new(*index);
-            sprite.flip_x = desc.flip_x;
-            sprite.flip_y = desc.flip_y;
+            sprite.flip_x = desc.flip_x != orientation.flip_x;
+            sprite.flip_y = desc.flip_y != orientation.flip_y;
+            sprite.color = desc.color;
             let atlas = &res.handle_map[&desc.sheet];
             commands.entity(ent).insert_bundle(SpriteSheetBundle {
                 sprite: sprite,
@@ -91,6 +138,16 @@ impl GameAssetsPlugin {
     ) {
         let sprite_desc = fs::read_to_string("assets/graphics_desc.ron").unwrap();
 
+        //Without rules every wall and floor just uses its unconnected sprite
+        let autotile: AutotileRules = match from_str(&sprite_desc) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Failed to load autotile rules, using defaults: {}", e);
+                AutotileRules::default()
+            }
+        };
+        commands.insert_resource(autotile);
+
         let sprite_desc: GraphicsDesc = match from_str(&sprite_desc) {
             Ok(x) => x,
             Err(e) => {
@@ -8,19 +8,59 @@ mod ascii;
 mod assets;
 mod particles;
 mod pixel_perfect_selection;
+mod selection;
 mod ui;
 
-pub use ascii::spawn_ascii_sprite;
+pub use ascii::{spawn_ascii_sprite, spawn_ascii_text, spawn_nine_slice};
 pub use particles::*;
 
-#[derive(Inspectable, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Inspectable, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum Orientation {
     Up,
+    #[default]
     Down,
     Left,
     Right,
 }
 
+impl Orientation {
+    /// Next orientation clockwise
+    pub fn rotated(self) -> Self {
+        match self {
+            Orientation::Up => Orientation::Right,
+            Orientation::Right => Orientation::Down,
+            Orientation::Down => Orientation::Left,
+            Orientation::Left => Orientation::Up,
+        }
+    }
+
+    /// Nearest orientation to a rotation about z
+    pub fn from_rotation(rotation: Quat) -> Self {
+        let facing = rotation.mul_vec3(Vec3::X);
+        if facing.x.abs() >= facing.y.abs() {
+            if facing.x >= 0.0 {
+                Orientation::Right
+            } else {
+                Orientation::Left
+            }
+        } else if facing.y > 0.0 {
+            Orientation::Up
+        } else {
+            Orientation::Down
+        }
+    }
+
+    /// Radians counter clockwise from Right
+    pub fn angle(self) -> f32 {
+        match self {
+            Orientation::Right => 0.0,
+            Orientation::Up => std::f32::consts::FRAC_PI_2,
+            Orientation::Left => std::f32::consts::PI,
+            Orientation::Down => -std::f32::consts::FRAC_PI_2,
+        }
+    }
+}
+
 #[derive(
     Inspectable, Component, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy,
 )]
@@ -29,28 +69,59 @@ pub enum Graphic {
     WorldObject(WorldObject),
 }
 
-impl Default for Orientation {
-    fn default() -> Self {
-        Orientation::Down
-    }
-}
-
 #[derive(
-    Inspectable, Deserialize, Component, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy,
+    Inspectable, Deserialize, Component, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy, Default,
 )]
 pub enum WorldObject {
+    #[default]
     Canister,
     CanisterMachine,
     SmallLabel(usize),
     Terminal(Orientation),
-    Wall(WallConnection),
+    Wall(WallMaterial, WallConnection),
     Furance(bool),
+    Heater(bool),
+    Cooler(bool),
+    Thermostat,
+    Vent,
+    Scrubber,
+    Pipe(WallConnection),
+    Pump(bool),
+    Valve(bool),
+    PipeConnector,
+    Generator,
+    Battery,
+    Electrolyzer(bool),
+    Unpowered,
+    Wire(WallConnection),
+    SolarPanel,
+    FurnaceGenerator,
+    Door(bool),
+    Floor(FloorMaterial, WallConnection),
 }
 
-impl Default for WorldObject {
-    fn default() -> Self {
-        WorldObject::Canister
-    }
+/// Component: Flips of an autotiled sprite on top of those in its SpriteDesc
+#[derive(Component, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct TileOrientation {
+    #[serde(default)]
+    pub flip_x: bool,
+    #[serde(default)]
+    pub flip_y: bool,
+}
+
+/// Entry in the autotile table of graphics_desc.ron
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct AutotileRule {
+    pub connection: WallConnection,
+    #[serde(default)]
+    pub orientation: TileOrientation,
+}
+
+/// Resource: Autotile tables for walls and floors from graphics_desc.ron, keyed by the 8 neighbour mask from autotile_mask
+#[derive(Deserialize, Clone, Default)]
+pub struct AutotileRules {
+    autotile: HashMap<u8, AutotileRule>,
+    floor_autotile: HashMap<u8, AutotileRule>,
 }
 
 #[derive(Default)]
@@ -83,18 +154,58 @@ pub struct SpriteDesc {
     flip_x: bool,
     #[serde(default)]
     flip_y: bool,
+    #[serde(default)]
+    color: Color,
 }
 
 #[derive(Component, Default)]
-pub struct Clickable {
-    just_clicked: bool,
+pub struct Clickable;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClickButton {
+    Left,
+    Right,
+    HoverEnter,
+    HoverExit,
+}
+
+//Sent for the topmost clickable under the mouse, local_pixel is measured from the sprite's top left
+#[derive(Debug, Clone, Copy)]
+pub struct EntityClicked {
+    pub entity: Entity,
+    pub button: ClickButton,
+    pub world_pos: Vec2,
+    pub local_pixel: (usize, usize),
+}
+
+//Topmost clickable under the mouse this frame
+#[derive(Default)]
+pub struct HoveredEntity(pub Option<Entity>);
+
+//Selected objects in the order they were picked, drag_start is set while a box is being dragged
+#[derive(Default)]
+pub struct Selection {
+    pub entities: Vec<Entity>,
+    pub drag_start: Option<Vec2>,
 }
 
+//Mirrors Selection so the inspector can query it
+#[derive(Component)]
+pub struct Selected;
+
+#[derive(Default)]
+struct HitboxCache {
+    map: HashMap<Graphic, PixelPerfectHitBox>,
+}
+
+//Mask is indexed [x][y] in image space, y down, before any flip
 #[derive(Component)]
 pub struct PixelPerfectHitBox {
     width: usize,
     height: usize,
     mask: Vec<Vec<bool>>,
+    flip_x: bool,
+    flip_y: bool,
 }
 
 struct AsciiPlugin {
@@ -113,6 +224,9 @@ pub struct ParticleSpawner {
     particle_size: Option<ParticleSize>,
     particle_velocity: Option<ParticleVelocity>,
     particle_color: Option<ParticleColor>,
+    //Paused spawners let their particles die out without making more
+    #[serde(default)]
+    pub paused: bool,
 }
 
 //XXX Timer can not be deserialized
@@ -125,8 +239,12 @@ pub struct ParticleSpawnerTimer {
 #[derive(Component)]
 pub struct GasText;
 
+#[derive(Component)]
+pub struct Tooltip;
+
 struct GameAssetsPlugin;
 struct PixelPerfectPlugin;
+struct SelectionPlugin;
 struct ParticlePlugin;
 struct UiPlugin;
 
@@ -139,12 +257,13 @@ impl PluginGroup for GraphicsPluginGroup {
             .add(GameAssetsPlugin)
             .add(ParticlePlugin)
             .add(UiPlugin)
-            .add(PixelPerfectPlugin);
+            .add(PixelPerfectPlugin)
+            .add(SelectionPlugin);
     }
 }
 
-//Internal Resource holding all handles and indices
-struct Graphics {
+/// Resource: Handles and indices of every sprite, only the colours are read outside graphics
+pub struct Graphics {
     handle_map: HashMap<SpriteSheet, Handle<TextureAtlas>>,
     graphics_map: HashMap<Graphic, (SpriteDesc, usize)>,
 }
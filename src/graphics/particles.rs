@@ -114,6 +114,9 @@ fn update_spawners(
     time: Res<Time>,
 ) {
     for (ent, spawner, mut timer) in spawners.iter_mut() {
+        if spawner.paused {
+            continue;
+        }
         timer.timer.tick(time.delta());
         if timer.timer.just_finished() {
             for _ in 0..spawner.amount_per_burst {
@@ -133,10 +136,7 @@ fn spawn_particle(
     spawner: &ParticleSpawner,
 ) -> Entity {
     let mut sprite = Sprite::default();
-    let image = match image {
-        Some(image) => image,
-        None => Handle::<Image>::default(),
-    };
+    let image = image.unwrap_or_default();
     let particle = commands
         .spawn_bundle(SpriteBundle {
             transform: Transform::from_xyz(
@@ -178,7 +178,7 @@ pub fn spawn_particle_spawner(
     config: &str,
     position: Vec3,
     assets: &AssetServer,
-) {
+) -> Entity {
     //let spawner = comp_from_config!(ParticleSpawner, "config/smoke_particle_spawner.ron");
     let spawner = comp_from_config!(ParticleSpawner, config);
     let spawner_ent = commands
@@ -204,4 +204,5 @@ pub fn spawn_particle_spawner(
             commands.entity(spawner_ent).add_child(ent);
         }
     }
+    spawner_ent
 }
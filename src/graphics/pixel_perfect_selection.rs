@@ -1,61 +1,285 @@
-use bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat::Rgba8UnormSrgb};
 use bevy::{asset::LoadState, prelude::*, utils::HashMap};
 
 use super::Graphics;
-use super::{Graphic, PixelPerfectPlugin};
+use super::{Graphic, HitboxCache, PixelPerfectPlugin};
 use crate::mouse::MousePosition;
 use crate::prelude::*;
 
-#[derive(Default)]
-struct HitboxCache {
-    map: HashMap<Graphic, PixelPerfectHitBox>,
-}
+#[derive(Component)]
+struct Outline;
+
+//Outline image is white and tinted by the sprite
+const HOVER_COLOR: Color = Color::rgb(1.0, 0.9, 0.35);
+const SELECTED_COLOR: Color = Color::rgb(0.4, 0.8, 1.0);
 
 impl Plugin for PixelPerfectPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(create_hitbox_cache)
             .insert_resource(HitboxCache::default())
-            .add_system(test_hitbox);
+            .insert_resource(HoveredEntity::default())
+            .add_event::<EntityClicked>()
+            //Before update so clicks are seen by everything in the same frame
+            .add_system_to_stage(CoreStage::PreUpdate, test_hitbox.after("mouse_position"))
+            .add_system(update_outlines);
+    }
+}
+
+impl PixelPerfectHitBox {
+    pub fn new(mask: Vec<Vec<bool>>, flip_x: bool, flip_y: bool) -> Self {
+        let width = mask.len();
+        let height = mask.first().map_or(0, |column| column.len());
+        Self {
+            width,
+            height,
+            mask,
+            flip_x,
+            flip_y,
+        }
+    }
+
+    /// Flips the sprite is drawn with, those of its SpriteDesc combined with the tile's own
+    pub fn flips(&self, orientation: TileOrientation) -> (bool, bool) {
+        (
+            self.flip_x != orientation.flip_x,
+            self.flip_y != orientation.flip_y,
+        )
+    }
+
+    /// Image pixel under world_pos for a sprite drawn with transform and orientation, if it is opaque
+    pub fn pixel_at(
+        &self,
+        transform: &GlobalTransform,
+        orientation: TileOrientation,
+        world_pos: Vec2,
+    ) -> Option<(usize, usize)> {
+        //Undo translation, rotation and scale, sprites are centered on their transform
+        let local = transform
+            .compute_matrix()
+            .inverse()
+            .transform_point3(world_pos.extend(transform.translation.z));
+        let x = (local.x + self.width as f32 / 2.0).floor();
+        //invert y
+        let y = (self.height as f32 / 2.0 - local.y).floor();
+        if x < 0.0 || x >= self.width as f32 || y < 0.0 || y >= self.height as f32 {
+            return None;
+        }
+
+        let mut x = x as usize;
+        let mut y = y as usize;
+        let (flip_x, flip_y) = self.flips(orientation);
+        if flip_x {
+            x = self.width - x - 1;
+        }
+        if flip_y {
+            y = self.height - y - 1;
+        }
+        self.mask[x][y].then_some((x, y))
+    }
+
+    /// True if any opaque pixel's center lies inside the world space box from min to max
+    pub fn overlaps(
+        &self,
+        transform: &GlobalTransform,
+        orientation: TileOrientation,
+        min: Vec2,
+        max: Vec2,
+    ) -> bool {
+        let matrix = transform.compute_matrix();
+        let (flip_x, flip_y) = self.flips(orientation);
+        for (x, column) in self.mask.iter().enumerate() {
+            for (y, solid) in column.iter().enumerate() {
+                if !solid {
+                    continue;
+                }
+                //Back from image space to where the pixel is drawn
+                let drawn_x = if flip_x { self.width - x - 1 } else { x };
+                let drawn_y = if flip_y { self.height - y - 1 } else { y };
+                let local = Vec3::new(
+                    drawn_x as f32 + 0.5 - self.width as f32 / 2.0,
+                    self.height as f32 / 2.0 - drawn_y as f32 - 0.5,
+                    0.0,
+                );
+                let world = matrix.transform_point3(local).truncate();
+                if world.cmpge(min).all() && world.cmple(max).all() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Mask one pixel larger on every side, set on the empty pixels that touch the sprite
+    pub fn outline(&self) -> Vec<Vec<bool>> {
+        let solid = |x: isize, y: isize| {
+            x >= 0
+                && y >= 0
+                && (x as usize) < self.width
+                && (y as usize) < self.height
+                && self.mask[x as usize][y as usize]
+        };
+        let mut outline = vec![vec![false; self.height + 2]; self.width + 2];
+        for (x, column) in outline.iter_mut().enumerate() {
+            for (y, pixel) in column.iter_mut().enumerate() {
+                let (x, y) = (x as isize - 1, y as isize - 1);
+                *pixel = !solid(x, y)
+                    && [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                        .iter()
+                        .any(|(dx, dy)| solid(x + dx, y + dy));
+            }
+        }
+        outline
     }
 }
 
-//TODO handle rotations and x/y flip
 fn test_hitbox(
-    mut query: Query<(&GlobalTransform, &Graphic, &Name, &mut Clickable)>,
+    query: Query<(Entity, &GlobalTransform, &Graphic, Option<&TileOrientation>), With<Clickable>>,
     hitboxes: Res<HitboxCache>,
     mouse: Res<MousePosition>,
     buttons: Res<Input<MouseButton>>,
+    mut hovered: ResMut<HoveredEntity>,
+    mut clicks: EventWriter<EntityClicked>,
+    mut last_hover: Local<Option<EntityClicked>>,
 ) {
-    if buttons.just_released(MouseButton::Left) {
-        let mut over_anything = false;
-        for (transform, graphic, name, mut click) in query.iter_mut() {
-            if let Some(hit_box) = hitboxes.map.get(graphic) {
-                //x and y are centered
-                let x_offset = transform.translation.x - hit_box.width as f32 / 2.0;
-                let y_offset = transform.translation.y - hit_box.height as f32 / 2.0;
-
-                let rel_x = (mouse.0.x - x_offset) as isize;
-                let rel_y = (mouse.0.y - y_offset) as isize;
-
-                if rel_x >= 0
-                    && rel_x < hit_box.width as isize
-                    && rel_y >= 0
-                    && rel_y < hit_box.height as isize
-                {
-                    //invert y
-                    let rel_y = hit_box.height as isize - rel_y - 1;
-                    if hit_box.mask[rel_x as usize][rel_y as usize] {
-                        over_anything = true;
-                        click.just_clicked = true;
-                        println!("Over {}!", name.as_str());
-                    }
-                }
-            }
+    //Only the object drawn in front gets the click
+    let mut topmost: Option<(f32, EntityClicked)> = None;
+    for (entity, transform, graphic, orientation) in query.iter() {
+        let orientation = orientation.copied().unwrap_or_default();
+        let local_pixel = match hitboxes
+            .map
+            .get(graphic)
+            .and_then(|hit_box| hit_box.pixel_at(transform, orientation, mouse.0))
+        {
+            Some(pixel) => pixel,
+            None => continue,
+        };
+        if topmost.is_none_or(|(z, _)| transform.translation.z > z) {
+            topmost = Some((
+                transform.translation.z,
+                EntityClicked {
+                    entity,
+                    button: ClickButton::HoverEnter,
+                    world_pos: mouse.0,
+                    local_pixel,
+                },
+            ));
         }
-        if !over_anything {
-            println!("Over Nothing");
+    }
+    let hit = topmost.map(|(_, hit)| hit);
+    let hit_entity = hit.map(|hit| hit.entity);
+
+    if hovered.0 != hit_entity {
+        if let Some(exit) = last_hover.take() {
+            clicks.send(EntityClicked {
+                button: ClickButton::HoverExit,
+                ..exit
+            });
+        }
+        if let Some(enter) = hit {
+            clicks.send(enter);
+        }
+        hovered.0 = hit_entity;
+    }
+    *last_hover = hit;
+
+    if let Some(hit) = hit {
+        if buttons.just_released(MouseButton::Left) {
+            clicks.send(EntityClicked {
+                button: ClickButton::Left,
+                ..hit
+            });
+        }
+        if buttons.just_released(MouseButton::Right) {
+            clicks.send(EntityClicked {
+                button: ClickButton::Right,
+                ..hit
+            });
+        }
+    }
+}
+
+//Outlines the hovered and selected objects, rebuilt when either or their graphics change
+fn update_outlines(
+    mut commands: Commands,
+    hovered: Res<HoveredEntity>,
+    selection: Res<Selection>,
+    graphic_query: Query<(&Graphic, Option<&TileOrientation>)>,
+    outline_query: Query<Entity, With<Outline>>,
+    hitboxes: Res<HitboxCache>,
+    mut images: ResMut<Assets<Image>>,
+    mut outlines: Local<HashMap<Graphic, Handle<Image>>>,
+    mut shown: Local<Vec<(Entity, Graphic, TileOrientation, bool)>>,
+) {
+    let targets: Vec<(Entity, Graphic, TileOrientation, bool)> = selection
+        .entities
+        .iter()
+        .map(|ent| (*ent, false))
+        .filter(|(ent, _)| hovered.0 != Some(*ent))
+        .chain(hovered.0.map(|ent| (ent, true)))
+        .filter_map(|(ent, hover)| {
+            let (graphic, orientation) = graphic_query.get(ent).ok()?;
+            let orientation = orientation.copied().unwrap_or_default();
+            hitboxes
+                .map
+                .contains_key(graphic)
+                .then_some((ent, *graphic, orientation, hover))
+        })
+        .collect();
+    if *shown == targets {
+        return;
+    }
+    for outline in outline_query.iter() {
+        commands.entity(outline).despawn_recursive();
+    }
+
+    for (ent, graphic, orientation, hover) in targets.iter() {
+        let hit_box = &hitboxes.map[graphic];
+        let (flip_x, flip_y) = hit_box.flips(*orientation);
+        let image = outlines
+            .entry(*graphic)
+            .or_insert_with(|| images.add(outline_image(hit_box)))
+            .clone();
+        //Child of the object so it follows its rotation and scale
+        let outline = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    flip_x,
+                    flip_y,
+                    color: if *hover { HOVER_COLOR } else { SELECTED_COLOR },
+                    ..default()
+                },
+                texture: image,
+                transform: Transform::from_xyz(0.0, 0.0, 0.05),
+                ..default()
+            })
+            .insert(Outline)
+            .insert(Name::new("Outline"))
+            .id();
+        commands.entity(*ent).add_child(outline);
+    }
+    *shown = targets;
+}
+
+fn outline_image(hit_box: &PixelPerfectHitBox) -> Image {
+    let outline = hit_box.outline();
+    let width = hit_box.width + 2;
+    let height = hit_box.height + 2;
+    let mut data = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for column in outline.iter() {
+            data.extend(if column[y] { [255; 4] } else { [0; 4] });
         }
     }
+    Image::new(
+        Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        Rgba8UnormSrgb,
+    )
 }
 
 fn create_hitbox_cache(
@@ -73,20 +297,20 @@ fn create_hitbox_cache(
 
             if server.get_load_state(desc_texture.clone()) == LoadState::Loaded {
                 let image = image_assets.get(desc_texture).unwrap();
-                add_graphic_to_hitboxes(&mut cache, graphic, desc.min, desc.max, image);
+                add_graphic_to_hitboxes(&mut cache, graphic, desc, image);
             }
         }
     }
 }
 
-//TODO support fliped images
 fn add_graphic_to_hitboxes(
     cache: &mut HitboxCache,
     graphic: &Graphic,
-    min: Vec2,
-    max: Vec2,
+    desc: &SpriteDesc,
     image: &Image,
 ) {
+    let min = desc.min;
+    let max = desc.max;
     assert!(Rgba8UnormSrgb == image.texture_descriptor.format);
 
     let width = max.x as usize - min.x as usize;
@@ -113,10 +337,6 @@ fn add_graphic_to_hitboxes(
 
     cache.map.insert(
         *graphic,
-        PixelPerfectHitBox {
-            width,
-            height,
-            mask,
-        },
+        PixelPerfectHitBox::new(mask, desc.flip_x, desc.flip_y),
     );
 }
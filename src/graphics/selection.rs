@@ -0,0 +1,161 @@
+use bevy::utils::HashSet;
+
+use crate::{mouse::MousePosition, prelude::*};
+
+use super::{HitboxCache, SelectionPlugin};
+
+//Drags smaller than this are clicks
+const MIN_DRAG: f32 = 2.0;
+
+#[derive(Component)]
+struct SelectionBox;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Selection::default())
+            .add_startup_system(spawn_selection_box)
+            .add_system(click_select)
+            .add_system(box_select.label("box_select").after(click_select))
+            //delete_selection is in the build module since it edits the station
+            .add_system(mark_selected.after("delete_selection"));
+    }
+}
+
+fn spawn_selection_box(mut commands: Commands) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.4, 0.8, 1.0, 0.25),
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(SelectionBox)
+        .insert(Name::new("SelectionBox"));
+}
+
+fn shift_held(keyboard: &Input<KeyCode>) -> bool {
+    keyboard.pressed(KeyCode::LShift) || keyboard.pressed(KeyCode::RShift)
+}
+
+//Clicking an object selects it, shift toggles it in the selection instead
+fn click_select(
+    mut selection: ResMut<Selection>,
+    mut clicks: EventReader<EntityClicked>,
+    hovered: Res<HoveredEntity>,
+    buttons: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<MousePosition>,
+    build: Res<BuildMode>,
+) {
+    //Clicks place things in build mode, and the end of a real drag is left to box_select
+    let dragged = selection
+        .drag_start
+        .is_some_and(|start| (mouse.0 - start).abs().max_element() >= MIN_DRAG);
+    if dragged || build.active {
+        return;
+    }
+    for click in clicks.iter() {
+        if click.button != ClickButton::Left {
+            continue;
+        }
+        if !shift_held(&keyboard) {
+            selection.entities = vec![click.entity];
+        } else if let Some(index) = selection.entities.iter().position(|e| *e == click.entity) {
+            selection.entities.remove(index);
+        } else {
+            selection.entities.push(click.entity);
+        }
+    }
+    //Clicking empty space drops the selection
+    if buttons.just_released(MouseButton::Left)
+        && hovered.0.is_none()
+        && !shift_held(&keyboard)
+        && !selection.entities.is_empty()
+    {
+        selection.entities.clear();
+    }
+}
+
+//Dragging draws a box, everything with a solid pixel inside it is selected
+fn box_select(
+    mut selection: ResMut<Selection>,
+    mut box_query: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<SelectionBox>>,
+    object_query: Query<
+        (Entity, &GlobalTransform, &Graphic, Option<&TileOrientation>),
+        With<Clickable>,
+    >,
+    hitboxes: Res<HitboxCache>,
+    mouse: Res<MousePosition>,
+    buttons: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    build: Res<BuildMode>,
+) {
+    if buttons.just_pressed(MouseButton::Left) && !build.active {
+        selection.drag_start = Some(mouse.0);
+    }
+    let start = match selection.drag_start {
+        Some(start) => start,
+        None => return,
+    };
+    let min = start.min(mouse.0);
+    let max = start.max(mouse.0);
+
+    let (mut transform, mut sprite, mut visibility) = box_query.single_mut();
+    if buttons.pressed(MouseButton::Left) {
+        transform.translation = ((min + max) / 2.0).extend(900.0);
+        sprite.custom_size = Some(max - min);
+        visibility.is_visible = true;
+        return;
+    }
+
+    visibility.is_visible = false;
+    selection.drag_start = None;
+    if (max - min).max_element() < MIN_DRAG {
+        return;
+    }
+    if !shift_held(&keyboard) {
+        selection.entities.clear();
+    }
+    for (ent, transform, graphic, orientation) in object_query.iter() {
+        let orientation = orientation.copied().unwrap_or_default();
+        let inside = hitboxes
+            .map
+            .get(graphic)
+            .is_some_and(|hit_box| hit_box.overlaps(transform, orientation, min, max));
+        if inside && !selection.entities.contains(&ent) {
+            selection.entities.push(ent);
+        }
+    }
+}
+
+//Keeps the Selected markers in step and drops entities that were despawned
+fn mark_selected(
+    mut commands: Commands,
+    mut selection: ResMut<Selection>,
+    exists_query: Query<Entity>,
+    selected_query: Query<Entity, With<Selected>>,
+) {
+    if selection
+        .entities
+        .iter()
+        .any(|e| exists_query.get(*e).is_err())
+    {
+        selection.entities.retain(|e| exists_query.get(*e).is_ok());
+    }
+    if !selection.is_changed() {
+        return;
+    }
+    let selected: HashSet<Entity> = selection.entities.iter().copied().collect();
+    for ent in selected_query.iter() {
+        if !selected.contains(&ent) {
+            commands.entity(ent).remove::<Selected>();
+        }
+    }
+    for ent in selection.entities.iter() {
+        if selected_query.get(*ent).is_err() {
+            commands.entity(*ent).insert(Selected);
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use crate::prelude::*;
+use crate::{mouse::MousePosition, prelude::*};
 
 use super::UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(ui_setup)
-            .add_startup_system(setup_gas_ui);
+            .add_startup_system(setup_gas_ui)
+            .add_startup_system(setup_tooltip)
+            .add_system(update_tooltip);
     }
 }
 
@@ -43,6 +45,127 @@ fn setup_gas_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
         .insert(GasText);
 }
 
+fn setup_tooltip(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("QuattrocentoSans-Bold.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(Tooltip)
+        .insert(Name::new("Tooltip"));
+}
+
+//Name and state of the hovered object, or the atmosphere of the tile under the mouse
+fn update_tooltip(
+    mut tooltip_query: Query<(&mut Text, &mut Style, &mut Visibility), With<Tooltip>>,
+    object_query: Query<(
+        &Name,
+        Option<&Canister>,
+        Option<&Furnace>,
+        Option<&PowerConsumer>,
+        Option<&Battery>,
+        Option<&Vent>,
+        Option<&Pump>,
+        Option<&Valve>,
+    )>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    tile_query: Query<&GasMixture>,
+    hovered: Res<HoveredEntity>,
+    mouse: Res<MousePosition>,
+    windows: Res<Windows>,
+    gases: Res<GasRegistry>,
+) {
+    let (mut text, mut style, mut visibility) = tooltip_query.single_mut();
+    let cursor = windows
+        .get_primary()
+        .and_then(|window| window.cursor_position());
+
+    let tooltip = match (hovered.0, cursor) {
+        (_, None) => None,
+        (Some(ent), _) => object_query.get(ent).ok().map(
+            |(name, canister, furnace, consumer, battery, vent, pump, valve)| {
+                let mut lines = vec![name.to_string()];
+                if let Some(canister) = canister {
+                    lines.push(canister.gases.describe(canister.volume as f64, &gases));
+                }
+                if let Some(furnace) = furnace {
+                    lines.push(format!(
+                        "{} {:.1}kg fuel {:.0}K",
+                        if furnace.burning { "Burning" } else { "Out" },
+                        furnace.fuel,
+                        furnace.temperature
+                    ));
+                }
+                if let Some(vent) = vent {
+                    lines.push(format!("Target {:.2} atm", vent.target_pressure));
+                }
+                if let Some(pump) = pump {
+                    lines.push(if pump.on { "On" } else { "Off" }.to_string());
+                }
+                if let Some(valve) = valve {
+                    lines.push(if valve.open { "Open" } else { "Closed" }.to_string());
+                }
+                if let Some(battery) = battery {
+                    lines.push(format!(
+                        "{:.0}/{:.0} kJ",
+                        battery.charge / 1000.0,
+                        battery.capacity / 1000.0
+                    ));
+                }
+                if let Some(consumer) = consumer {
+                    lines.push(format!("Power {:.0}%", consumer.supply * 100.0));
+                }
+                lines.join("\n")
+            },
+        ),
+        (None, _) => {
+            let (grid, grid_transform) = grid_query.single();
+            world_to_tile(
+                mouse.0,
+                grid_transform.translation.truncate(),
+                grid.tile_size,
+            )
+            .map(|(i, j)| {
+                let tile = tile_query.get(grid.grid[i][j]).unwrap();
+                format!("Tile {}, {}\n{}", i, j, tile.describe(TILE_VOLUME, &gases))
+            })
+        }
+    };
+
+    match (tooltip, cursor) {
+        (Some(tooltip), Some(cursor)) => {
+            if text.sections[0].value != tooltip {
+                text.sections[0].value = tooltip;
+            }
+            //Ui is positioned from the bottom left like the cursor
+            style.position = Rect {
+                left: Val::Px(cursor.x + 16.0),
+                bottom: Val::Px(cursor.y),
+                ..default()
+            };
+            visibility.is_visible = true;
+        }
+        _ => {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+        }
+    }
+}
+
 pub fn ui_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     // UI camera
     commands.spawn_bundle(UiCameraBundle::default());
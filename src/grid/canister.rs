@@ -5,7 +5,7 @@ use crate::{grid::CanisterPlugin, prelude::*};
 
 impl Plugin for CanisterPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_canister)
+        app.add_startup_system(spawn_canisters)
             .add_startup_system(spawn_canister_machine)
             .add_system(update_canister_graphics)
             .register_inspectable::<Canister>()
@@ -69,34 +69,43 @@ fn spawn_canister_machine(mut commands: Commands) {
         .entity(ent)
         .insert(CanisterMachine::default())
         .insert(Name::new("Machine"))
-        .insert(Clickable::default())
+        .insert(PowerConsumer {
+            watts: 100.0,
+            ..Default::default()
+        })
+        .insert(Clickable)
         .insert(Transform::from_xyz(32.0, 10.0, 300.0));
 }
 
-fn spawn_canister(mut commands: Commands) {
-    let ent = commands
-        .spawn()
-        .insert(Graphic::WorldObject(WorldObject::Canister))
-        .id();
+fn spawn_canisters(mut commands: Commands, gases: Res<GasRegistry>) {
+    let oxygen = gases.get("Oxygen").expect("Oxygen missing from gases.ron");
+    spawn_canister(
+        &mut commands,
+        Vec3::new(6.0, 10.0, 300.0),
+        GasMixture::single_gas(oxygen, 500.0, 293.0),
+    );
+}
+
+pub fn spawn_canister(commands: &mut Commands, position: Vec3, gases: GasMixture) -> Entity {
     let label = commands
         .spawn()
         .insert(Graphic::WorldObject(WorldObject::SmallLabel(0)))
-        .id();
-    commands
-        .entity(label)
         .insert(Label { id: 0, states: 8 })
-        .insert(Transform::from_xyz(0.01, -1.01, 0.01));
+        .insert(Transform::from_xyz(0.01, -1.01, 0.01))
+        .id();
     commands
-        .entity(ent)
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Canister))
         .insert(Canister {
-            gases: GasMixture::single_gas(Gas::Oxygen, 500.0, 293.0),
+            gases,
             volume: 0.5,
             max_pressure: 10.0,
         })
-        .insert(Clickable::default())
-        .insert(Transform::from_xyz(06.0, 10.0, 300.0))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
         .insert(Name::new("Canister"))
-        .add_child(label);
+        .add_child(label)
+        .id()
 }
 
 fn update_small_label(percent_full: f32, states: usize, sprite: &mut Graphic) {
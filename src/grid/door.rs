@@ -0,0 +1,54 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(toggle_door)
+            .add_system(update_door_graphics)
+            .register_inspectable::<Door>();
+    }
+}
+
+pub fn spawn_door(
+    commands: &mut Commands,
+    position: Vec3,
+    facing: Orientation,
+    id: usize,
+) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Door(false)))
+        .insert(Door { id, open: false })
+        .insert(Name::new(format!("Door {}", id)))
+        .insert(Clickable)
+        .insert(
+            Transform::from_translation(position)
+                .with_rotation(Quat::from_rotation_z(facing.angle())),
+        )
+        .id()
+}
+
+fn toggle_door(
+    mut door_query: Query<&mut Door>,
+    mut clicks: EventReader<EntityClicked>,
+    build: Res<BuildMode>,
+) {
+    for click in clicks.iter() {
+        //Clicks place and take down things in build mode
+        if click.button != ClickButton::Left || build.active {
+            continue;
+        }
+        if let Ok(mut door) = door_query.get_mut(click.entity) {
+            door.open = !door.open;
+        }
+    }
+}
+
+fn update_door_graphics(mut door_query: Query<(&Door, &mut Graphic), Changed<Door>>) {
+    for (door, mut graphic) in door_query.iter_mut() {
+        *graphic = Graphic::WorldObject(WorldObject::Door(door.open));
+    }
+}
@@ -0,0 +1,83 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::{Floor, FloorGrid, FloorMaterial, FloorPlugin, WallGrid, GRID_SIZE};
+
+//Under the walls, which sit at 0 on the grid
+const FLOOR_Z: f32 = -1.0;
+
+impl Plugin for FloorPlugin {
+    fn build(&self, app: &mut App) {
+        //Floors are laid with the walls in spawn_walls
+        app.add_system_to_stage(CoreStage::PostUpdate, floor_update)
+            .register_inspectable::<Floor>();
+    }
+}
+
+pub fn create_floor(
+    commands: &mut Commands,
+    floors: &mut FloorGrid,
+    tile_size: f32,
+    x: usize,
+    y: usize,
+    material: FloorMaterial,
+) -> Option<Entity> {
+    if floors.floors[x][y].is_none() {
+        let floor = commands
+            .spawn()
+            .insert(Graphic::WorldObject(WorldObject::Floor(
+                material,
+                WallConnection::None,
+            )))
+            .insert(TileOrientation::default())
+            .insert(Transform::from_xyz(
+                x as f32 * tile_size,
+                y as f32 * tile_size,
+                FLOOR_Z,
+            ))
+            .insert(Floor { material })
+            .insert(Name::new("Floor"))
+            .id();
+        floors.floors[x][y] = Some(floor);
+        return Some(floor);
+    }
+    None
+}
+
+//Floors only join up with floors of the same material
+fn floor_update(
+    mut floor_query: Query<(&mut Graphic, &mut TileOrientation, &Floor)>,
+    grid_query: Query<&FloorGrid, (Changed<FloorGrid>, With<WallGrid>)>,
+    rules: Res<AutotileRules>,
+) {
+    for grid in grid_query.iter() {
+        let mut by_material: HashMap<FloorMaterial, [[Option<Entity>; GRID_SIZE]; GRID_SIZE]> =
+            HashMap::default();
+        for (i, row) in grid.floors.iter().enumerate() {
+            for (j, ent) in row.iter().enumerate() {
+                if let Some((_, _, floor)) = ent.and_then(|ent| floor_query.get(ent).ok()) {
+                    by_material
+                        .entry(floor.material)
+                        .or_insert([[None; GRID_SIZE]; GRID_SIZE])[i][j] = *ent;
+                }
+            }
+        }
+
+        for (i, row) in grid.floors.iter().enumerate() {
+            for (j, ent) in row.iter().enumerate() {
+                if let Some(ent) = ent {
+                    if let Ok((mut graphic, mut orientation, floor)) = floor_query.get_mut(*ent) {
+                        let rule =
+                            rules.floor_rule(autotile_mask(&by_material[&floor.material], i, j));
+                        *graphic = Graphic::WorldObject(WorldObject::Floor(
+                            floor.material,
+                            rule.connection,
+                        ));
+                        *orientation = rule.orientation;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,28 +1,39 @@
+use bevy::tasks::ComputeTaskPool;
 use bevy_inspector_egui::RegisterInspectable;
 
 use crate::prelude::*;
 
-use super::{GasPlugin, WallGrid, GRID_SIZE};
+use super::{FloorGrid, GasPlugin, WallGrid, GRID_SIZE};
 
 impl Plugin for GasPlugin {
     fn build(&self, app: &mut App) {
+        let gases = comp_from_config!(GasRegistry, "config/gases.ron");
+        assert!(
+            gases.len() <= MAX_GAS_COUNT,
+            "gases.ron has more than {} gases",
+            MAX_GAS_COUNT
+        );
         //TODO use bevy 0.7 label systems
-        app.add_startup_system(spawn_gas_grid)
+        app.insert_resource(gases)
+            .add_startup_system(spawn_gas_grid)
             .add_system(diffuse_gas_grid)
             .add_system(gas_wall_connection)
-            //.add_system(heat_gas)
             //.add_system(print_total)
-            .add_system(gas_clamp)
+            .add_system(warn_over_pressure)
+            .add_event::<OverPressureEvent>()
             .register_inspectable::<GasVisualizationSettings>()
+            .register_inspectable::<GasSolver>()
+            .register_inspectable::<PressureLimits>()
             .register_inspectable::<GasMixture>();
     }
 }
 
-fn gas_clamp(mut gas_query: Query<&mut GasMixture, Changed<GasMixture>>) {
-    for mut tile in gas_query.iter_mut() {
-        for amount in tile.amount.iter_mut() {
-            *amount = amount.clamp(0.0, 200.0);
-        }
+fn warn_over_pressure(mut events: EventReader<OverPressureEvent>) {
+    for event in events.iter() {
+        warn!(
+            "Tile {:?} is over safe pressure at {:.2} atms",
+            event.tile, event.pressure
+        );
     }
 }
 
@@ -37,101 +48,106 @@ fn print_total(tile_query: Query<&GasMixture>, grid_query: Query<&GasGrid>) {
     println!("Total {:.1}", total);
 }
 
-#[allow(dead_code)]
-fn heat_gas(mut tile_query: Query<&mut GasMixture>, grid_query: Query<&GasGrid>, time: Res<Time>) {
-    if time.time_since_startup().as_secs() < 5 {
-        let grid = grid_query.iter().next();
-        let mut tile = tile_query.get_mut(grid.unwrap().grid[23][23]).unwrap();
-        tile.temperature += 500.0 * time.delta_seconds() as f64;
-    }
-}
-
-fn gas_wall_connection(mut gas_query: Query<&mut GasGrid>, wall_query: Query<&WallGrid>) {
+//Gas on tiles that become walled is pushed out so no moles are lost under the wall
+fn gas_wall_connection(
+    mut gas_query: Query<(&mut GasGrid, &GlobalTransform)>,
+    mut tile_query: Query<&mut GasMixture>,
+    wall_query: Query<(&WallGrid, &FloorGrid)>,
+    material_query: Query<&Wall>,
+    door_query: Query<(&GlobalTransform, &Door)>,
+    gases: Res<GasRegistry>,
+) {
     //TODO handle multi grids/walls
     //maybe a struct linking the 2
     //gas grids should be made by or from wall grid
-    let walls = wall_query.single();
-    let mut grid = gas_query.single_mut();
+    let (walls, floors) = wall_query.single();
+    let (mut grid, grid_transform) = gas_query.single_mut();
+    let mut wall_mask = [[false; GRID_SIZE]; GRID_SIZE];
     for (i, row) in walls.walls.iter().enumerate() {
         for (j, ent) in row.iter().enumerate() {
-            grid.wall_mask[i][j] = ent.is_some();
+            wall_mask[i][j] = ent.is_some();
+            //Walls are spawned with the grid in the walls stage, metal is only a fallback
+            grid.thermal[i][j] = match ent {
+                Some(ent) => material_query
+                    .get(*ent)
+                    .map(|wall| wall.material)
+                    .unwrap_or_default()
+                    .thermal(),
+                None => Thermal::GAS,
+            };
         }
     }
-}
 
-//XXX does temp diffuse based on the material...
-fn diffuse_temperature(
-    i: usize,
-    j: usize,
-    x0: &[[GasMixture; GRID_SIZE]; GRID_SIZE],
-    x: &mut [[GasMixture; GRID_SIZE]; GRID_SIZE],
-    wall_mask: &[[bool; GRID_SIZE]; GRID_SIZE],
-    a: f64,
-) {
-    let up_wall = j == 0 || wall_mask[i][j - 1];
-    let down_wall = j == GRID_SIZE - 1 || wall_mask[i][j + 1];
-    let left_wall = i == 0 || wall_mask[i - 1][j];
-    let right_wall = i == GRID_SIZE - 1 || wall_mask[i + 1][j];
-    let wall_count =
-        !up_wall as isize + !down_wall as isize + !left_wall as isize + !right_wall as isize;
-    let mut new_x = x0[i][j].temperature;
-    if !left_wall {
-        new_x += a * x[i - 1][j].temperature;
+    //Closed doors seal like metal walls
+    for (transform, door) in door_query.iter() {
+        if door.open {
+            continue;
+        }
+        if let Some((i, j)) = world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        ) {
+            wall_mask[i][j] = true;
+            grid.thermal[i][j] = WallMaterial::Metal.thermal();
+        }
     }
-    if !right_wall {
-        new_x += a * x[i + 1][j].temperature;
+
+    let mut walled = Vec::new();
+    for (i, (row, old_row)) in wall_mask.iter().zip(grid.wall_mask.iter()).enumerate() {
+        for (j, (wall, old_wall)) in row.iter().zip(old_row.iter()).enumerate() {
+            if *wall && !old_wall {
+                walled.push((i, j));
+            }
+        }
     }
-    if !up_wall {
-        new_x += a * x[i][j - 1].temperature;
+    grid.wall_mask = wall_mask;
+
+    let mut floor_mask = [[false; GRID_SIZE]; GRID_SIZE];
+    for (mask_row, row) in floor_mask.iter_mut().zip(floors.floors.iter()) {
+        for (floored, floor) in mask_row.iter_mut().zip(row.iter()) {
+            *floored = floor.is_some();
+        }
     }
-    if !down_wall {
-        new_x += a * x[i][j + 1].temperature;
+    grid.space_mask = *space_mask(&grid.wall_mask, &floor_mask);
+
+    if walled.is_empty() {
+        return;
     }
-    new_x /= 1.0 + wall_count as f64 * a;
-    x[i][j].temperature = new_x;
+    let mut tiles = grid.mixtures(&tile_query);
+    let edited = wall_over_gas(&mut tiles, &grid.wall_mask, &walled, &gases);
+    grid.write_mixtures(&tiles, &edited, &mut tile_query);
 }
 
-//TODO should diffuse pressure but thats a bit complex, just multipling rate by temperature breaks the invariant that a tile loses as much as another gains
-fn diffuse_moles(
-    i: usize,
-    j: usize,
-    x0: &[[GasMixture; GRID_SIZE]; GRID_SIZE],
-    x: &mut [[GasMixture; GRID_SIZE]; GRID_SIZE],
-    wall_mask: &[[bool; GRID_SIZE]; GRID_SIZE],
-    a: f64,
-    gas: usize,
-) {
-    let up_wall = j == 0 || wall_mask[i][j - 1];
-    let down_wall = j == GRID_SIZE - 1 || wall_mask[i][j + 1];
-    let left_wall = i == 0 || wall_mask[i - 1][j];
-    let right_wall = i == GRID_SIZE - 1 || wall_mask[i + 1][j];
-    let wall_count =
-        !up_wall as isize + !down_wall as isize + !left_wall as isize + !right_wall as isize;
-    let mut new_x = x0[i][j].amount[gas];
-    if !left_wall {
-        new_x += a * x[i - 1][j].amount[gas] * x[i - 1][j].temperature;
-    }
-    if !right_wall {
-        new_x += a * x[i + 1][j].amount[gas] * x[i + 1][j].temperature;
+/// Sums each gas colour from the registry weighted by how full that gas is, 1.0 being full
+fn mixture_color(gases: &GasRegistry, fullness: impl Fn(Gas) -> f64) -> Color {
+    let mut color = Vec3::ZERO;
+    for (gas, desc) in gases.iter() {
+        let desc_color = Vec3::new(desc.color.r(), desc.color.g(), desc.color.b());
+        color += desc_color * (fullness(gas) as f32).clamp(0.0, 1.0);
     }
-    if !up_wall {
-        new_x += a * x[i][j - 1].amount[gas] * x[i][j - 1].temperature;
-    }
-    if !down_wall {
-        new_x += a * x[i][j + 1].amount[gas] * x[i][j + 1].temperature;
-    }
-    new_x /= 1.0 + wall_count as f64 * (a * x[i][j].temperature);
-    *x[i][j].amount.get_mut(gas).unwrap() = new_x;
+    let color = color.min(Vec3::ONE);
+    Color::rgba(color.x, color.y, color.z, 0.25)
 }
 
 //TODO optimize, grids probably dont need be all the same massive size, maybe map is many smaller grids with interfaces
 //Thanks Jos Stam! http://graphics.cs.cmu.edu/nsp/course/15-464/Fall09/papers/StamFluidforGames.pdf
-fn diffuse_gas_grid(
+/// One frame of the gas grids, public so the benches can run it in an App
+pub fn diffuse_gas_grid(
     mut tile_query: Query<(&mut GasMixture, &mut TextureAtlasSprite)>,
-    grid_query: Query<(&GasGrid, &GasVisualizationSettings)>,
+    mut grid_query: Query<(
+        Entity,
+        &mut GasGrid,
+        &GasVisualizationSettings,
+        &GasSolver,
+        &PressureLimits,
+    )>,
+    mut over_pressure: EventWriter<OverPressureEvent>,
+    gases: Res<GasRegistry>,
+    pool: Res<ComputeTaskPool>,
     time: Res<Time>,
 ) {
-    for (grid, visualization) in grid_query.iter() {
+    for (grid_ent, mut grid, visualization, solver, limits) in grid_query.iter_mut() {
         //Copy tiles XXX FIXME bad
         let mut x0 = [[GasMixture::default(); GRID_SIZE]; GRID_SIZE];
         let mut x = [[GasMixture::default(); GRID_SIZE]; GRID_SIZE];
@@ -143,25 +159,18 @@ fn diffuse_gas_grid(
             }
         }
 
-        let a = time.delta_seconds() as f64 * 0.0006 * (GRID_SIZE * GRID_SIZE) as f64;
-        for _k in 0..30 {
-            for i in 0..(GRID_SIZE) {
-                for j in 0..(GRID_SIZE) {
-                    diffuse_temperature(i, j, &x0, &mut x, &grid.wall_mask, a);
-                }
-            }
-        }
-
-        let a = time.delta_seconds() as f64 * 0.005;
-        for _k in 0..50 {
-            for i in 0..(GRID_SIZE) {
-                for j in 0..(GRID_SIZE) {
-                    for gas in 0..GAS_COUNT {
-                        diffuse_moles(i, j, &x0, &mut x, &grid.wall_mask, a, gas);
-                    }
-                }
-            }
-        }
+        diffuse_step(
+            &x0,
+            &mut x,
+            &grid.wall_mask,
+            &grid.thermal,
+            &gases,
+            time.delta_seconds() as f64,
+            *solver,
+            &pool,
+        );
+        relieve_pressure(&mut x, &grid.wall_mask, &grid.max_pressure, &gases);
+        vent_to_space(&mut x, &grid.space_mask);
 
         #[allow(clippy::needless_range_loop)]
         for i in 0..GRID_SIZE {
@@ -169,28 +178,29 @@ fn diffuse_gas_grid(
                 let (mut gas, mut sprite) = tile_query.get_mut(grid.grid[i][j]).unwrap();
                 gas.amount = x[i][j].amount;
                 gas.temperature = x[i][j].temperature;
+
+                let pressure = gas.get_total_pressure();
+                let is_unsafe = !grid.wall_mask[i][j] && pressure as f64 > limits.safe_pressure;
+                if is_unsafe && !grid.unsafe_mask[i][j] {
+                    over_pressure.send(OverPressureEvent {
+                        grid: grid_ent,
+                        tile: (i, j),
+                        pressure: pressure,
+                    });
+                }
+                grid.unsafe_mask[i][j] = is_unsafe;
                 if !grid.wall_mask[i][j] {
                     match *visualization {
                         GasVisualizationSettings::None => {
                             sprite.color = Color::NONE;
                         }
                         GasVisualizationSettings::Pressure => {
-                            sprite.color = Color::rgba(
-                                (gas.get_pressure(Gas::Oxygen) as f32 / 1.5).clamp(0.0, 1.0),
-                                (gas.get_pressure(Gas::Nitrogen) as f32 / 1.5).clamp(0.0, 1.0),
-                                (gas.get_pressure(Gas::CarbonDioxide) as f32 / 1.0).clamp(0.0, 1.0),
-                                0.25,
-                            );
+                            sprite.color =
+                                mixture_color(&gases, |gas_type| gas.get_pressure(gas_type) / 1.5);
                         }
                         GasVisualizationSettings::Moles => {
-                            sprite.color = Color::rgba(
-                                ((gas.amount[Gas::Oxygen as usize] - 75.0) as f32 / 15.0)
-                                    .clamp(0.0, 1.0),
-                                (gas.amount[Gas::Nitrogen as usize] as f32 / 100.0).clamp(0.0, 1.0),
-                                (gas.amount[Gas::CarbonDioxide as usize] as f32 / 100.0)
-                                    .clamp(0.0, 1.0),
-                                0.25,
-                            );
+                            sprite.color =
+                                mixture_color(&gases, |gas_type| gas.amount[gas_type.0] / 100.0);
                         }
                         GasVisualizationSettings::Temperature => {
                             sprite.color = Color::rgba(
@@ -210,10 +220,15 @@ fn diffuse_gas_grid(
 }
 
 fn spawn_gas_grid(mut commands: Commands, ascii: Res<AsciiSheet>) {
+    let limits = comp_from_config!(PressureLimits);
     let mut gas_grid = GasGrid {
         //XXX find a better default entity
         grid: [[Entity::from_raw(0); GRID_SIZE]; GRID_SIZE],
         wall_mask: [[false; GRID_SIZE]; GRID_SIZE],
+        thermal: [[Thermal::GAS; GRID_SIZE]; GRID_SIZE],
+        unsafe_mask: [[false; GRID_SIZE]; GRID_SIZE],
+        space_mask: [[false; GRID_SIZE]; GRID_SIZE],
+        max_pressure: [[limits.max_pressure; GRID_SIZE]; GRID_SIZE],
         tile_size: 32.0,
     };
     for x in 0..GRID_SIZE {
@@ -231,8 +246,7 @@ fn spawn_gas_grid(mut commands: Commands, ascii: Res<AsciiSheet>) {
                 Vec3::splat(1.0),
             );
             let gas = GasMixture {
-                //https://www.discovermagazine.com/the-sciences/how-cold-is-it-in-outer-space
-                temperature: 2.7,
+                temperature: SPACE_TEMPERATURE,
                 ..Default::default()
             };
             gas_grid.grid[x][y] = commands.entity(sprite).insert(gas).id()
@@ -251,5 +265,7 @@ fn spawn_gas_grid(mut commands: Commands, ascii: Res<AsciiSheet>) {
         .insert(gas_grid)
         .insert(GlobalTransform::default())
         .insert(GasVisualizationSettings::None)
+        .insert(GasSolver::GaussSeidel)
+        .insert(limits)
         .insert(Name::new("Gas Grid"));
 }
@@ -2,55 +2,186 @@ use crate::prelude::*;
 use bevy::app::PluginGroupBuilder;
 
 mod canister;
+mod door;
+mod floor;
 mod gas;
+mod pipe;
+mod radiation;
+mod solver;
 mod wall;
 
+pub use canister::spawn_canister;
+pub use door::spawn_door;
+pub use gas::diffuse_gas_grid;
+pub use pipe::{pipe_flow, pump_gas};
+pub use solver::{
+    diffuse_step, displace_gas, displacement_targets, neighbours, new_boxed_grid, reclaim_gas,
+    relieve_pressure, space_mask, unwall_gas, vent_to_space, wall_over_gas,
+};
+pub use wall::{autotile, autotile_mask, line_autotile, line_tiles, rectangle_tiles, wall_repairs};
+
 pub const GRID_SIZE: usize = 50;
 pub const IDEAL_GAS_CONST: f64 = 8.314462618153 /* m^3*Pa/K*mol */ * (1.0/101325.0); //atm/Pa
 pub const TILE_VOLUME: f64 = 2.0; // m^3
+                                  //https://www.discovermagazine.com/the-sciences/how-cold-is-it-in-outer-space
+pub const SPACE_TEMPERATURE: f64 = 2.7; // K
+pub const STEFAN_BOLTZMANN: f64 = 5.670374419e-8; // W/m^2*K^4
+
+pub const MAX_GAS_COUNT: usize = 16;
+pub const BASE_DIFFUSION_RATE: f64 = 0.005;
+pub const AIR_MOLAR_MASS: f64 = 28.97; // g/mol
+/// Index of a gas in the GasRegistry, GasTiles contain all registered gases
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Gas(pub usize);
+
+/// Entry in config/gases.ron
+#[derive(Deserialize, Clone, Debug)]
+pub struct GasDesc {
+    pub name: String,
+    pub molar_mass: f64,    // g/mol
+    pub heat_capacity: f64, // J/mol*K
+    pub color: Color,
+    pub toxicity: f64,
+    //Overrides the rate from molar mass
+    #[serde(default)]
+    pub diffusion_rate: Option<f64>,
+}
 
-pub const GAS_COUNT: usize = 7;
-//Possible gas types, GasTiles contain all of these
-#[derive(Inspectable, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Gas {
-    None = 0,
-    Oxygen,
-    Nitrogen,
-    CarbonDioxide,
-    Helium3,
-    Hydrogen,
-    WaterVapor,
+/// Resource: All gases in the simulation, loaded from config/gases.ron
+#[derive(Deserialize, Clone, Debug)]
+pub struct GasRegistry {
+    gases: Vec<GasDesc>,
 }
 
-impl Default for Gas {
-    fn default() -> Gas {
-        Gas::None
+impl GasRegistry {
+    pub fn get(&self, name: &str) -> Option<Gas> {
+        self.gases
+            .iter()
+            .position(|desc| desc.name == name)
+            .map(Gas)
+    }
+
+    pub fn desc(&self, gas: Gas) -> &GasDesc {
+        &self.gases[gas.0]
+    }
+
+    /// Graham's law, lighter gases spread faster, air diffuses at BASE_DIFFUSION_RATE
+    pub fn diffusion_rate(&self, gas: Gas) -> f64 {
+        let desc = self.desc(gas);
+        desc.diffusion_rate
+            .unwrap_or_else(|| BASE_DIFFUSION_RATE * (AIR_MOLAR_MASS / desc.molar_mass).sqrt())
+    }
+
+    pub fn len(&self) -> usize {
+        self.gases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gases.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Gas, &GasDesc)> {
+        self.gases
+            .iter()
+            .enumerate()
+            .map(|(i, desc)| (Gas(i), desc))
     }
 }
 
 /// Component: Tile containing moles of gas and the temperature
 #[derive(Component, Clone, Copy, Default, Inspectable, Deserialize)]
 pub struct GasMixture {
-    pub amount: [f64; GAS_COUNT],
+    pub amount: [f64; MAX_GAS_COUNT],
     pub temperature: f64,
 }
 
 impl GasMixture {
     pub fn single_gas(gas: Gas, amount: f32, temperature: f32) -> GasMixture {
         let mut mixture = GasMixture::default();
-        mixture.amount[gas as usize] = amount as f64;
+        mixture.amount[gas.0] = amount as f64;
         mixture.temperature = temperature as f64;
         mixture
     }
 
-    #[allow(dead_code)]
     pub fn get_pressure(&self, gas: Gas) -> f64 {
-        self.amount[gas as usize] * self.temperature * IDEAL_GAS_CONST / TILE_VOLUME
+        self.amount[gas.0] * self.temperature * IDEAL_GAS_CONST / TILE_VOLUME
+    }
+    pub fn total_moles(&self) -> f64 {
+        self.amount.iter().sum()
+    }
+
+    /// Mixes in a fraction of another mixture, temperatures are averaged by heat capacity so
+    /// no heat is made or lost
+    pub fn add_fraction(&mut self, other: &GasMixture, fraction: f64, gases: &GasRegistry) {
+        let heat_capacity = self.heat_capacity(gases);
+        let added = other.heat_capacity(gases) * fraction;
+        if heat_capacity + added > 0.0 {
+            self.temperature = (self.temperature * heat_capacity + other.temperature * added)
+                / (heat_capacity + added);
+        }
+        for (amount, other) in self.amount.iter_mut().zip(other.amount.iter()) {
+            *amount += other * fraction;
+        }
     }
+
+    /// J/K of all the gas in the mixture
+    pub fn heat_capacity(&self, gases: &GasRegistry) -> f64 {
+        gases
+            .iter()
+            .map(|(gas, desc)| self.amount[gas.0] * desc.heat_capacity)
+            .sum()
+    }
+
+    /// Adds joules of heat to the gas, negative to remove it, does nothing to a vacuum
+    pub fn add_heat(&mut self, joules: f64, gases: &GasRegistry) {
+        let heat_capacity = self.heat_capacity(gases);
+        if heat_capacity > 0.0 {
+            self.temperature = (self.temperature + joules / heat_capacity).max(SPACE_TEMPERATURE);
+        }
+    }
+
+    /// Pressure of the mixture held in volume m^3
+    pub fn pressure(&self, volume: f64) -> f64 {
+        self.total_moles() * self.temperature * IDEAL_GAS_CONST / volume
+    }
+
+    /// Partial pressure of each gas on a tile weighted by how toxic it is
+    pub fn toxicity(&self, gases: &GasRegistry) -> f64 {
+        gases
+            .iter()
+            .map(|(gas, desc)| self.get_pressure(gas) * desc.toxicity)
+            .sum()
+    }
+
+    /// Pressure and temperature, then a line for each gas present
+    pub fn describe(&self, volume: f64, gases: &GasRegistry) -> String {
+        let mut text = format!("{:.2} atm {:.0}K", self.pressure(volume), self.temperature);
+        for (gas, desc) in gases.iter() {
+            //Skip the traces diffusion leaves everywhere
+            if self.amount[gas.0] >= 0.05 {
+                text += &format!("\n{}: {:.1} mol", desc.name, self.amount[gas.0]);
+            }
+        }
+        text
+    }
+
+    /// Moves moles of this mixture, in proportion to its gases, into other
+    pub fn move_moles(&mut self, other: &mut GasMixture, moles: f64, gases: &GasRegistry) {
+        let total = self.total_moles();
+        if total <= 0.0 || moles <= 0.0 {
+            return;
+        }
+        let fraction = (moles / total).min(1.0);
+        other.add_fraction(self, fraction, gases);
+        for amount in self.amount.iter_mut() {
+            *amount -= *amount * fraction;
+        }
+    }
+
     pub fn get_total_pressure(&self) -> f32 {
         let mut total = 0.0;
-        for i in 0..GAS_COUNT {
-            total += self.amount[i] * self.temperature * IDEAL_GAS_CONST / TILE_VOLUME
+        for amount in self.amount.iter() {
+            total += amount * self.temperature * IDEAL_GAS_CONST / TILE_VOLUME
         }
         total as f32
     }
@@ -61,9 +192,110 @@ impl GasMixture {
 pub struct GasGrid {
     pub grid: [[Entity; GRID_SIZE]; GRID_SIZE],
     pub wall_mask: [[bool; GRID_SIZE]; GRID_SIZE],
+    pub thermal: [[Thermal; GRID_SIZE]; GRID_SIZE],
+    pub unsafe_mask: [[bool; GRID_SIZE]; GRID_SIZE],
+    //Open tiles with no floor that space can reach, gas there is lost
+    pub space_mask: [[bool; GRID_SIZE]; GRID_SIZE],
+    //Atms each tile holds before gas spills into its neighbours
+    pub max_pressure: [[f64; GRID_SIZE]; GRID_SIZE],
     pub tile_size: f32,
 }
 
+impl GasGrid {
+    /// Copies the gas on every tile out of the world so it can be edited as a grid
+    pub fn mixtures(
+        &self,
+        tile_query: &Query<&mut GasMixture>,
+    ) -> Box<[[GasMixture; GRID_SIZE]; GRID_SIZE]> {
+        let mut tiles = new_boxed_grid(GasMixture::default());
+        for (row, entities) in tiles.iter_mut().zip(self.grid.iter()) {
+            for (tile, entity) in row.iter_mut().zip(entities.iter()) {
+                *tile = *tile_query.get(*entity).unwrap();
+            }
+        }
+        tiles
+    }
+
+    /// Writes the edited tiles from mixtures back to the world
+    pub fn write_mixtures(
+        &self,
+        tiles: &[[GasMixture; GRID_SIZE]; GRID_SIZE],
+        edited: &[(usize, usize)],
+        tile_query: &mut Query<&mut GasMixture>,
+    ) {
+        for &(i, j) in edited {
+            *tile_query.get_mut(self.grid[i][j]).unwrap() = tiles[i][j];
+        }
+    }
+
+    /// Open tiles reachable from start without crossing a wall, start included
+    pub fn room(&self, start: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut visited = [[false; GRID_SIZE]; GRID_SIZE];
+        let mut to_visit = vec![start];
+        let mut room = Vec::new();
+        visited[start.0][start.1] = true;
+        while let Some((i, j)) = to_visit.pop() {
+            room.push((i, j));
+            for (ni, nj) in neighbours(i, j, GRID_SIZE) {
+                if !visited[ni][nj] && !self.wall_mask[ni][nj] {
+                    visited[ni][nj] = true;
+                    to_visit.push((ni, nj));
+                }
+            }
+        }
+        room
+    }
+
+    /// Every set of open tiles closed off from the edge of the grid, in scan order
+    pub fn rooms(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = [[false; GRID_SIZE]; GRID_SIZE];
+        let mut rooms = Vec::new();
+        for i in 0..GRID_SIZE {
+            for j in 0..GRID_SIZE {
+                if seen[i][j] || self.wall_mask[i][j] {
+                    continue;
+                }
+                let room = self.room((i, j));
+                for &(x, y) in room.iter() {
+                    seen[x][y] = true;
+                }
+                let open_to_space = room
+                    .iter()
+                    .any(|&(x, y)| x == 0 || y == 0 || x == GRID_SIZE - 1 || y == GRID_SIZE - 1);
+                if !open_to_space {
+                    rooms.push(room);
+                }
+            }
+        }
+        rooms
+    }
+}
+
+/// Tile under a world position for a grid at map_pos, None when off the grid
+pub fn world_to_tile(position: Vec2, map_pos: Vec2, tile_size: f32) -> Option<(usize, usize)> {
+    let x = (position.x - map_pos.x + tile_size * 0.5) / tile_size;
+    let y = (position.y - map_pos.y + tile_size * 0.5) / tile_size;
+    if x < 0.0 || y < 0.0 || x >= GRID_SIZE as f32 || y >= GRID_SIZE as f32 {
+        return None;
+    }
+    Some((x as usize, y as usize))
+}
+
+/// Component: Pressure limits loaded from config, every tile's GasGrid.max_pressure starts at
+/// max_pressure and tiles above safe_pressure send an OverPressureEvent
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct PressureLimits {
+    pub max_pressure: f64,
+    pub safe_pressure: f64,
+}
+
+/// Event: A gas tile went above the safe pressure
+pub struct OverPressureEvent {
+    pub grid: Entity,
+    pub tile: (usize, usize),
+    pub pressure: f32,
+}
+
 /// Component: Grid holding Wall entities
 #[derive(Component)]
 pub struct WallGrid {
@@ -71,15 +303,150 @@ pub struct WallGrid {
     pub walls: [[Option<Entity>; GRID_SIZE]; GRID_SIZE],
 }
 
-/// Tag
+/// Component: Floor layer under the walls, on the same entity as the WallGrid and using its tile size
+#[derive(Component)]
+pub struct FloorGrid {
+    pub floors: [[Option<Entity>; GRID_SIZE]; GRID_SIZE],
+}
+
+/// Component: Floor tile, gas can sit over it even when it is open to space
+#[derive(Component, Inspectable, Default, Clone, Copy)]
+pub struct Floor {
+    pub material: FloorMaterial,
+}
+
+#[derive(Inspectable, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum FloorMaterial {
+    #[default]
+    Plating,
+    Carpet,
+    Grating,
+}
+
+/// Event: Changes to the WallGrid, applied together in the "walls" stage after Update
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WallEvent {
+    Place {
+        tile: (usize, usize),
+        material: WallMaterial,
+    },
+    Remove {
+        tile: (usize, usize),
+    },
+}
+
+/// Fix for a WallGrid that disagrees with the Wall entities
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WallRepair {
+    //Grid points at an entity that is not a wall
+    Clear((usize, usize)),
+    //Wall entity on an empty tile the grid does not know about
+    Insert((usize, usize), Entity),
+    //Wall entity on a tile that already has one, or off the grid
+    Despawn(Entity),
+}
+
+/// Component: Wall tile, the gas tile under it holds the wall temperature
+#[derive(Component, Inspectable, Default, Clone, Copy)]
+pub struct Wall {
+    pub material: WallMaterial,
+}
+
+/// Component: Seals its tile like a wall while closed, ids are what the console uses
+#[derive(Component, Inspectable, Clone, Copy, Default)]
+pub struct Door {
+    pub id: usize,
+    pub open: bool,
+}
+
+#[derive(Inspectable, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum WallMaterial {
+    Insulated,
+    #[default]
+    Metal,
+    Glass,
+    Reinforced,
+}
+
+/// How a tile passes and stores heat, relative to a tile of gas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thermal {
+    pub conductivity: f64,
+    pub heat_capacity: f64,
+}
+
+impl Thermal {
+    pub const GAS: Thermal = Thermal {
+        conductivity: 1.0,
+        heat_capacity: 1.0,
+    };
+}
+
+impl WallMaterial {
+    pub fn thermal(&self) -> Thermal {
+        match self {
+            WallMaterial::Insulated => Thermal {
+                conductivity: 0.0002,
+                heat_capacity: 2.0,
+            },
+            WallMaterial::Metal => Thermal {
+                conductivity: 0.005,
+                heat_capacity: 4.0,
+            },
+            WallMaterial::Glass => Thermal {
+                conductivity: 0.002,
+                heat_capacity: 3.0,
+            },
+            WallMaterial::Reinforced => Thermal {
+                conductivity: 0.001,
+                heat_capacity: 8.0,
+            },
+        }
+    }
+}
+
+/// Component: Grid holding Pipe entities, a second layer over the gas grid
 #[derive(Component)]
-pub struct Wall;
+pub struct PipeGrid {
+    pub tile_size: f32,
+    pub pipes: [[Option<Entity>; GRID_SIZE]; GRID_SIZE],
+}
+
+/// Component: Pipe segment holding its own GasMixture, loaded from config/pipe.ron
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Pipe {
+    pub volume: f64, // m^3
+    //Moles per second per atm of difference between neighbouring segments
+    pub conductance: f64,
+}
+
+/// Component: Pushes gas from the pipe behind it into the pipe it faces,
+/// the pipe under a pump only connects through it
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Pump {
+    pub rate: f64, // moles/s
+    //Stops pumping once the pipe in front reaches this many atms
+    pub max_pressure: f64,
+    pub on: bool,
+    #[serde(default)]
+    pub direction: Orientation,
+}
+
+/// Component: Cuts the pipe under it off from its neighbours while closed
+#[derive(Component, Inspectable, Clone, Copy, Default)]
+pub struct Valve {
+    pub open: bool,
+}
+
+/// Component: Joins the pipe under it to a canister on the same tile, feeds a vent on the same tile,
+/// or opens to the room if there is neither
+#[derive(Component, Inspectable, Clone, Copy, Default)]
+pub struct PipeConnector;
 
 #[derive(Component, Default, Inspectable, Deserialize)]
 //TODO mols, temp, pressure
-///
 pub struct Canister {
-    gases: GasMixture,
+    pub gases: GasMixture,
     pub volume: f32,
     pub max_pressure: f32,
 }
@@ -97,30 +464,63 @@ pub enum GasVisualizationSettings {
     Temperature,
 }
 
+/// Component: Solver used to step the gas grid, red black ordering splits each sweep across the task pool
+#[derive(Component, Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GasSolver {
+    GaussSeidel,
+    RedBlack,
+}
+
+/// Component: Radiation between exposed wall faces and space, loaded from config/radiation.ron
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Radiation {
+    pub emissivity: f64,
+    pub absorptivity: f64,
+    pub star_intensity: f64, // W/m^2
+    //Seconds for the star to circle the station once
+    pub day_length: f64,
+    pub face_area: f64, // m^2
+    //J/K of a wall per unit of Thermal heat_capacity
+    pub heat_capacity_scale: f64,
+}
+
 struct WallPlugin;
+struct FloorPlugin;
 struct GasPlugin;
+struct RadiationPlugin;
 struct CanisterPlugin;
+struct DoorPlugin;
+struct PipePlugin;
 
 pub struct GridPluginGroup;
 
 impl PluginGroup for GridPluginGroup {
     fn build(&mut self, group: &mut PluginGroupBuilder) {
-        group.add(WallPlugin).add(GasPlugin).add(CanisterPlugin);
+        group
+            .add(WallPlugin)
+            .add(FloorPlugin)
+            .add(GasPlugin)
+            .add(RadiationPlugin)
+            .add(CanisterPlugin)
+            .add(DoorPlugin)
+            .add(PipePlugin);
     }
 }
 
-#[derive(Inspectable, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Inspectable, Deserialize, Serialize, Hash, Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum WallConnection {
+    #[default]
     None,
     One,
     Corner,
     Cross,
     T,
     All,
-}
-
-impl Default for WallConnection {
-    fn default() -> Self {
-        WallConnection::None
-    }
+    OneVertical,
+    CrossVertical,
+    TVertical,
+    //Blob tiles, only picked when the corners between connected sides are set too
+    Fill,
+    Edge,
+    EdgeVertical,
 }
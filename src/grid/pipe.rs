@@ -0,0 +1,390 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::{wall::line_autotile, PipePlugin, GRID_SIZE};
+
+impl Plugin for PipePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_pipes)
+            .add_system(pipe_flow_update)
+            .add_system(toggle_pipe_fittings)
+            .add_system(update_pipe_graphics)
+            .add_system_to_stage(CoreStage::PostUpdate, pipe_update)
+            .register_inspectable::<Pipe>()
+            .register_inspectable::<Pump>()
+            .register_inspectable::<Valve>()
+            .register_inspectable::<PipeConnector>();
+    }
+}
+
+/// Moves gas from the higher pressure mixture to the lower, never past the point where the pressures match
+pub fn pipe_flow(
+    a: &mut GasMixture,
+    a_volume: f64,
+    b: &mut GasMixture,
+    b_volume: f64,
+    conductance: f64,
+    dt: f64,
+    gases: &GasRegistry,
+) {
+    let (from, from_volume, to, to_volume) = if a.pressure(a_volume) >= b.pressure(b_volume) {
+        (a, a_volume, b, b_volume)
+    } else {
+        (b, b_volume, a, a_volume)
+    };
+    let difference = from.pressure(from_volume) - to.pressure(to_volume);
+    if difference <= 0.0 {
+        return;
+    }
+    //Moles that would even out the pressures if they arrived at the source temperature
+    let to_even =
+        difference / (IDEAL_GAS_CONST * from.temperature * (1.0 / from_volume + 1.0 / to_volume));
+    from.move_moles(to, (conductance * difference * dt).min(to_even), gases);
+}
+
+/// Pushes up to moles from one mixture into another, stops once to reaches max_pressure
+pub fn pump_gas(
+    from: &mut GasMixture,
+    to: &mut GasMixture,
+    to_volume: f64,
+    moles: f64,
+    max_pressure: f64,
+    gases: &GasRegistry,
+) {
+    if from.temperature <= 0.0 {
+        return;
+    }
+    let room =
+        (max_pressure - to.pressure(to_volume)) * to_volume / (IDEAL_GAS_CONST * from.temperature);
+    from.move_moles(to, moles.min(room), gases);
+}
+
+pub fn spawn_pump(commands: &mut Commands, position: Vec3, direction: Orientation) -> Entity {
+    let mut pump = comp_from_config!(Pump);
+    pump.direction = direction;
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Pump(pump.on)))
+        .insert(pump)
+        .insert(Name::new("Pump"))
+        .insert(Clickable)
+        .insert(
+            Transform::from_translation(position)
+                .with_rotation(Quat::from_rotation_z(direction.angle())),
+        )
+        .id()
+}
+
+pub fn spawn_valve(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Valve(false)))
+        .insert(Valve { open: false })
+        .insert(Name::new("Valve"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+pub fn spawn_connector(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::PipeConnector))
+        .insert(PipeConnector)
+        .insert(Name::new("Pipe Connector"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+fn create_pipe(commands: &mut Commands, grid: &mut PipeGrid, x: usize, y: usize, pipe: Pipe) {
+    if grid.pipes[x][y].is_none() {
+        let ent = commands
+            .spawn()
+            .insert(Graphic::WorldObject(WorldObject::Pipe(
+                WallConnection::None,
+            )))
+            .insert(TileOrientation::default())
+            .insert(Transform::from_xyz(
+                x as f32 * grid.tile_size,
+                y as f32 * grid.tile_size,
+                0.0,
+            ))
+            .insert(pipe)
+            .insert(GasMixture {
+                temperature: SPACE_TEMPERATURE,
+                ..Default::default()
+            })
+            .insert(Name::new("Pipe"))
+            .id();
+        grid.pipes[x][y] = Some(ent);
+    }
+}
+
+fn spawn_pipes(mut commands: Commands) {
+    let mut grid = PipeGrid {
+        tile_size: 32.0,
+        pipes: [[None; GRID_SIZE]; GRID_SIZE],
+    };
+    let tile_size = grid.tile_size;
+    let offset = GRID_SIZE as f32 * tile_size / 2.0;
+    let fitting_position = |x: usize, y: usize| {
+        Vec3::new(
+            x as f32 * tile_size - offset,
+            y as f32 * tile_size - offset,
+            60.0,
+        )
+    };
+
+    //Runs from the canister to the vent in the starting room, the vent sits on the last connector
+    let pipe = comp_from_config!(Pipe);
+    for (x, y) in [(25, 25), (25, 24), (25, 23), (24, 23)] {
+        create_pipe(&mut commands, &mut grid, x, y, pipe);
+    }
+    spawn_connector(&mut commands, fitting_position(25, 25));
+    spawn_pump(&mut commands, fitting_position(25, 24), Orientation::Down);
+    spawn_valve(&mut commands, fitting_position(25, 23));
+    spawn_connector(&mut commands, fitting_position(24, 23));
+
+    let children: Vec<Entity> = grid.pipes.iter().flatten().flatten().copied().collect();
+    commands
+        .spawn()
+        .push_children(&children)
+        .insert(GlobalTransform::default())
+        .insert(Transform::from_xyz(-offset, -offset, 50.0))
+        .insert(grid)
+        .insert(Name::new("Pipe Grid"));
+}
+
+fn neighbour(tile: (usize, usize), direction: Orientation) -> Option<(usize, usize)> {
+    let (i, j) = tile;
+    match direction {
+        Orientation::Up if j < GRID_SIZE - 1 => Some((i, j + 1)),
+        Orientation::Down if j > 0 => Some((i, j - 1)),
+        Orientation::Left if i > 0 => Some((i - 1, j)),
+        Orientation::Right if i < GRID_SIZE - 1 => Some((i + 1, j)),
+        _ => None,
+    }
+}
+
+fn opposite(direction: Orientation) -> Orientation {
+    match direction {
+        Orientation::Up => Orientation::Down,
+        Orientation::Down => Orientation::Up,
+        Orientation::Left => Orientation::Right,
+        Orientation::Right => Orientation::Left,
+    }
+}
+
+//Copies the two mixtures out so both can be changed at once
+fn flow_between(
+    pipe_query: &mut Query<(&Pipe, &mut GasMixture)>,
+    a: Entity,
+    b: Entity,
+    dt: f64,
+    gases: &GasRegistry,
+) {
+    let (pipe_a, mut mixture_a) = match pipe_query.get(a) {
+        Ok((pipe, mixture)) => (*pipe, *mixture),
+        Err(_) => return,
+    };
+    let (pipe_b, mut mixture_b) = match pipe_query.get(b) {
+        Ok((pipe, mixture)) => (*pipe, *mixture),
+        Err(_) => return,
+    };
+    pipe_flow(
+        &mut mixture_a,
+        pipe_a.volume,
+        &mut mixture_b,
+        pipe_b.volume,
+        pipe_a.conductance.min(pipe_b.conductance),
+        dt,
+        gases,
+    );
+    *pipe_query.get_mut(a).unwrap().1 = mixture_a;
+    *pipe_query.get_mut(b).unwrap().1 = mixture_b;
+}
+
+//Pipe grid lines up with the gas grid so tiles index both
+fn pipe_flow_update(
+    pipe_grid_query: Query<(&PipeGrid, &GlobalTransform)>,
+    mut pipe_query: Query<(&Pipe, &mut GasMixture)>,
+    gas_grid_query: Query<&GasGrid>,
+    mut tile_query: Query<&mut GasMixture, Without<Pipe>>,
+    valve_query: Query<(&GlobalTransform, &Valve)>,
+    pump_query: Query<(&GlobalTransform, &Pump)>,
+    connector_query: Query<&GlobalTransform, With<PipeConnector>>,
+    mut canister_query: Query<(&GlobalTransform, &mut Canister)>,
+    vent_query: Query<&GlobalTransform, With<Vent>>,
+    gases: Res<GasRegistry>,
+    time: Res<Time>,
+) {
+    let (grid, grid_transform) = pipe_grid_query.single();
+    let gas_grid = gas_grid_query.single();
+    let dt = time.delta_seconds() as f64;
+    let tile_of = |transform: &GlobalTransform| {
+        world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        )
+    };
+
+    //Closed valves and pumps cut their pipe off from passive flow
+    let mut isolated = [[false; GRID_SIZE]; GRID_SIZE];
+    for (transform, valve) in valve_query.iter() {
+        if let Some((i, j)) = tile_of(transform) {
+            isolated[i][j] |= !valve.open;
+        }
+    }
+    for (transform, _) in pump_query.iter() {
+        if let Some((i, j)) = tile_of(transform) {
+            isolated[i][j] = true;
+        }
+    }
+
+    for i in 0..GRID_SIZE {
+        for j in 0..GRID_SIZE {
+            let ent = match grid.pipes[i][j] {
+                Some(ent) if !isolated[i][j] => ent,
+                _ => continue,
+            };
+            for (ni, nj) in [(i + 1, j), (i, j + 1)] {
+                if ni >= GRID_SIZE || nj >= GRID_SIZE || isolated[ni][nj] {
+                    continue;
+                }
+                if let Some(other) = grid.pipes[ni][nj] {
+                    flow_between(&mut pipe_query, ent, other, dt, &gases);
+                }
+            }
+        }
+    }
+
+    for (transform, pump) in pump_query.iter() {
+        if !pump.on {
+            continue;
+        }
+        let tile = match tile_of(transform) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let behind = neighbour(tile, opposite(pump.direction)).and_then(|(i, j)| grid.pipes[i][j]);
+        let front = neighbour(tile, pump.direction).and_then(|(i, j)| grid.pipes[i][j]);
+        if let (Some(behind), Some(front)) = (behind, front) {
+            let mut from = *pipe_query.get(behind).unwrap().1;
+            let (front_pipe, mut to) = match pipe_query.get(front) {
+                Ok((pipe, mixture)) => (*pipe, *mixture),
+                Err(_) => continue,
+            };
+            pump_gas(
+                &mut from,
+                &mut to,
+                front_pipe.volume,
+                pump.rate * dt,
+                pump.max_pressure,
+                &gases,
+            );
+            *pipe_query.get_mut(behind).unwrap().1 = from;
+            *pipe_query.get_mut(front).unwrap().1 = to;
+        }
+    }
+
+    for transform in connector_query.iter() {
+        let (i, j) = match tile_of(transform) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        //Vents on a connector draw from the pipe themselves in vent_output
+        if vent_query
+            .iter()
+            .any(|vent_transform| tile_of(vent_transform) == Some((i, j)))
+        {
+            continue;
+        }
+        let (pipe, mut mixture) =
+            match grid.pipes[i][j].and_then(|ent| pipe_query.get_mut(ent).ok()) {
+                Some(pipe) => pipe,
+                None => continue,
+            };
+
+        let canister = canister_query
+            .iter_mut()
+            .find(|(canister_transform, _)| tile_of(canister_transform) == Some((i, j)));
+        if let Some((_, mut canister)) = canister {
+            let volume = canister.volume as f64;
+            pipe_flow(
+                &mut mixture,
+                pipe.volume,
+                &mut canister.gases,
+                volume,
+                pipe.conductance,
+                dt,
+                &gases,
+            );
+        } else {
+            let mut tile = tile_query.get_mut(gas_grid.grid[i][j]).unwrap();
+            pipe_flow(
+                &mut mixture,
+                pipe.volume,
+                &mut tile,
+                TILE_VOLUME,
+                pipe.conductance,
+                dt,
+                &gases,
+            );
+        }
+    }
+}
+
+fn pipe_update(
+    mut pipe_query: Query<(&mut Graphic, &mut TileOrientation), With<Pipe>>,
+    grid_query: Query<&PipeGrid, Changed<PipeGrid>>,
+    rules: Res<AutotileRules>,
+) {
+    for grid in grid_query.iter() {
+        for (i, row) in grid.pipes.iter().enumerate() {
+            for (j, ent) in row.iter().enumerate() {
+                if let Some(ent) = ent {
+                    if let Ok((mut graphic, mut orientation)) = pipe_query.get_mut(*ent) {
+                        let rule = line_autotile(&rules, &grid.pipes, i, j);
+                        *graphic = Graphic::WorldObject(WorldObject::Pipe(rule.connection));
+                        *orientation = rule.orientation;
+                    }
+                }
+            }
+        }
+    }
+}
+
+//Clicking a pump switches it on or off, clicking a valve opens or closes it
+fn toggle_pipe_fittings(
+    mut pump_query: Query<&mut Pump>,
+    mut valve_query: Query<&mut Valve>,
+    mut clicks: EventReader<EntityClicked>,
+    build: Res<BuildMode>,
+) {
+    for click in clicks.iter() {
+        if click.button != ClickButton::Left || build.active {
+            continue;
+        }
+        if let Ok(mut pump) = pump_query.get_mut(click.entity) {
+            pump.on = !pump.on;
+        }
+        if let Ok(mut valve) = valve_query.get_mut(click.entity) {
+            valve.open = !valve.open;
+        }
+    }
+}
+
+fn update_pipe_graphics(
+    mut pump_query: Query<(&mut Graphic, &Pump), (Changed<Pump>, Without<Valve>)>,
+    mut valve_query: Query<(&mut Graphic, &Valve), Changed<Valve>>,
+) {
+    for (mut graphic, pump) in pump_query.iter_mut() {
+        *graphic = Graphic::WorldObject(WorldObject::Pump(pump.on));
+    }
+    for (mut graphic, valve) in valve_query.iter_mut() {
+        *graphic = Graphic::WorldObject(WorldObject::Valve(valve.open));
+    }
+}
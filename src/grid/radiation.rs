@@ -0,0 +1,83 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::{RadiationPlugin, GRID_SIZE};
+
+impl Plugin for RadiationPlugin {
+    fn build(&self, app: &mut App) {
+        //Gas grid has to exist to hold the settings
+        app.add_startup_system_to_stage(StartupStage::PostStartup, add_radiation)
+            .add_system(radiate_walls)
+            .register_inspectable::<Radiation>();
+    }
+}
+
+impl Radiation {
+    /// Unit vector from the station towards the star
+    pub fn star_direction(&self, seconds: f64) -> Vec2 {
+        let angle = std::f64::consts::TAU * seconds / self.day_length;
+        Vec2::new(angle.cos() as f32, angle.sin() as f32)
+    }
+
+    /// Watts into one face pointing along normal, negative when it loses more than it absorbs
+    pub fn face_power(&self, normal: Vec2, star: Vec2, temperature: f64) -> f64 {
+        let sunlight = self.absorptivity * self.star_intensity * normal.dot(star).max(0.0) as f64;
+        let emitted =
+            self.emissivity * STEFAN_BOLTZMANN * (temperature.powi(4) - SPACE_TEMPERATURE.powi(4));
+        (sunlight - emitted) * self.face_area
+    }
+}
+
+fn add_radiation(mut commands: Commands, grid_query: Query<Entity, With<GasGrid>>) {
+    for grid in grid_query.iter() {
+        commands.entity(grid).insert(comp_from_config!(Radiation));
+    }
+}
+
+//Only walls radiate, the gas behind them gets it through conduction
+fn radiate_walls(
+    grid_query: Query<(&GasGrid, &Radiation)>,
+    mut tile_query: Query<&mut GasMixture>,
+    time: Res<Time>,
+) {
+    for (grid, radiation) in grid_query.iter() {
+        let star = radiation.star_direction(time.seconds_since_startup());
+        //Off the grid or open to space, an empty room inside the hull still blocks the star
+        let is_space = |i: isize, j: isize| {
+            if i < 0 || j < 0 || i >= GRID_SIZE as isize || j >= GRID_SIZE as isize {
+                return true;
+            }
+            grid.space_mask[i as usize][j as usize]
+        };
+
+        let mut changes = Vec::new();
+        for i in 0..GRID_SIZE {
+            for j in 0..GRID_SIZE {
+                if !grid.wall_mask[i][j] {
+                    continue;
+                }
+                let temperature = tile_query.get(grid.grid[i][j]).unwrap().temperature;
+                let mut power = 0.0;
+                for normal in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+                    if is_space(
+                        i as isize + normal.x as isize,
+                        j as isize + normal.y as isize,
+                    ) {
+                        power += radiation.face_power(normal, star, temperature);
+                    }
+                }
+                if power != 0.0 {
+                    let heat_capacity =
+                        grid.thermal[i][j].heat_capacity * radiation.heat_capacity_scale;
+                    changes.push((i, j, power * time.delta_seconds() as f64 / heat_capacity));
+                }
+            }
+        }
+
+        for (i, j, change) in changes {
+            let mut tile = tile_query.get_mut(grid.grid[i][j]).unwrap();
+            tile.temperature = (tile.temperature + change).max(SPACE_TEMPERATURE);
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use bevy::tasks::{ParallelSlice, TaskPool};
+
+use crate::prelude::*;
+
+/// Allocates a square grid on the heap, large grids do not fit on the stack
+pub fn new_boxed_grid<T: Copy, const N: usize>(value: T) -> Box<[[T; N]; N]> {
+    vec![[value; N]; N]
+        .into_boxed_slice()
+        .try_into()
+        .unwrap_or_else(|_| unreachable!())
+}
+
+/// Side neighbours of tile i, j that are on an n by n grid
+pub fn neighbours(i: usize, j: usize, n: usize) -> impl Iterator<Item = (usize, usize)> {
+    [
+        (i > 0).then(|| (i - 1, j)),
+        (i + 1 < n).then(|| (i + 1, j)),
+        (j > 0).then(|| (i, j - 1)),
+        (j + 1 < n).then(|| (i, j + 1)),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Conductance between two tiles, heat has to pass through both halves
+fn conductance(a: &Thermal, b: &Thermal) -> f64 {
+    2.0 * a.conductivity * b.conductivity / (a.conductivity + b.conductivity)
+}
+
+//Walls take part through their material, each tile keeps C*T constant across a step
+fn diffuse_temperature<const N: usize>(
+    i: usize,
+    j: usize,
+    x0: &[[GasMixture; N]; N],
+    x: &[[GasMixture; N]; N],
+    thermal: &[[Thermal; N]; N],
+    a: f64,
+) -> f64 {
+    let this = &thermal[i][j];
+    let mut new_x = this.heat_capacity * x0[i][j].temperature;
+    let mut total_weight = this.heat_capacity;
+    for (ni, nj) in neighbours(i, j, N) {
+        let weight = a * conductance(this, &thermal[ni][nj]);
+        new_x += weight * x[ni][nj].temperature;
+        total_weight += weight;
+    }
+    new_x / total_weight
+}
+
+//TODO should diffuse pressure but thats a bit complex, just multipling rate by temperature breaks the invariant that a tile loses as much as another gains
+fn diffuse_moles<const N: usize>(
+    i: usize,
+    j: usize,
+    x0: &[[GasMixture; N]; N],
+    x: &[[GasMixture; N]; N],
+    wall_mask: &[[bool; N]; N],
+    a: f64,
+    gas: usize,
+) -> f64 {
+    //Gas never diffuses into a wall, whatever is sealed inside one stays put
+    if wall_mask[i][j] {
+        return x0[i][j].amount[gas];
+    }
+    let up_wall = j == 0 || wall_mask[i][j - 1];
+    let down_wall = j == N - 1 || wall_mask[i][j + 1];
+    let left_wall = i == 0 || wall_mask[i - 1][j];
+    let right_wall = i == N - 1 || wall_mask[i + 1][j];
+    let wall_count =
+        !up_wall as isize + !down_wall as isize + !left_wall as isize + !right_wall as isize;
+    let mut new_x = x0[i][j].amount[gas];
+    if !left_wall {
+        new_x += a * x[i - 1][j].amount[gas] * x[i - 1][j].temperature;
+    }
+    if !right_wall {
+        new_x += a * x[i + 1][j].amount[gas] * x[i + 1][j].temperature;
+    }
+    if !up_wall {
+        new_x += a * x[i][j - 1].amount[gas] * x[i][j - 1].temperature;
+    }
+    if !down_wall {
+        new_x += a * x[i][j + 1].amount[gas] * x[i][j + 1].temperature;
+    }
+    new_x / (1.0 + wall_count as f64 * (a * x[i][j].temperature))
+}
+
+/// Runs f on every tile of one checkerboard colour, tiles of a colour only read tiles
+/// of the other colour so each row can be solved on a different thread
+fn red_black_pass<const N: usize, T: Send + 'static>(
+    pool: &TaskPool,
+    rows: &[usize],
+    parity: usize,
+    f: impl Fn(usize, usize) -> T + Send + Sync,
+) -> Vec<Vec<(usize, usize, T)>> {
+    let chunk_size = (N / pool.thread_num().max(1)).max(1);
+    rows.par_chunk_map(pool, chunk_size, |chunk| {
+        let mut results = Vec::new();
+        for &i in chunk {
+            for j in ((i + parity) % 2..N).step_by(2) {
+                results.push((i, j, f(i, j)));
+            }
+        }
+        results
+    })
+}
+
+/// Advances the gas in x0 by dt seconds and writes the result to x, x is also the solvers starting guess
+pub fn diffuse_step<const N: usize>(
+    x0: &[[GasMixture; N]; N],
+    x: &mut [[GasMixture; N]; N],
+    wall_mask: &[[bool; N]; N],
+    thermal: &[[Thermal; N]; N],
+    gases: &GasRegistry,
+    dt: f64,
+    solver: GasSolver,
+    pool: &TaskPool,
+) {
+    let rows: Vec<usize> = (0..N).collect();
+
+    let a = dt * 0.0006 * (N * N) as f64;
+    for _k in 0..30 {
+        match solver {
+            GasSolver::GaussSeidel => {
+                for i in 0..N {
+                    for j in 0..N {
+                        x[i][j].temperature = diffuse_temperature(i, j, x0, x, thermal, a);
+                    }
+                }
+            }
+            GasSolver::RedBlack => {
+                for parity in 0..2 {
+                    let results = red_black_pass::<N, f64>(pool, &rows, parity, |i, j| {
+                        diffuse_temperature(i, j, x0, x, thermal, a)
+                    });
+                    for (i, j, temperature) in results.into_iter().flatten() {
+                        x[i][j].temperature = temperature;
+                    }
+                }
+            }
+        }
+    }
+
+    let rates: Vec<f64> = gases
+        .iter()
+        .map(|(gas, _)| dt * gases.diffusion_rate(gas))
+        .collect();
+    for _k in 0..50 {
+        match solver {
+            GasSolver::GaussSeidel => {
+                for i in 0..N {
+                    for j in 0..N {
+                        for (gas, a) in rates.iter().enumerate() {
+                            x[i][j].amount[gas] = diffuse_moles(i, j, x0, x, wall_mask, *a, gas);
+                        }
+                    }
+                }
+            }
+            GasSolver::RedBlack => {
+                for parity in 0..2 {
+                    let results =
+                        red_black_pass::<N, [f64; MAX_GAS_COUNT]>(pool, &rows, parity, |i, j| {
+                            let mut amount = [0.0; MAX_GAS_COUNT];
+                            for (gas, (amount, a)) in
+                                amount.iter_mut().zip(rates.iter()).enumerate()
+                            {
+                                *amount = diffuse_moles(i, j, x0, x, wall_mask, *a, gas);
+                            }
+                            amount
+                        });
+                    for (i, j, amount) in results.into_iter().flatten() {
+                        x[i][j].amount = amount;
+                    }
+                }
+            }
+        }
+    }
+}
+
+//Moles of source that take neighbour to exactly max_pressure once add_fraction mixes them in.
+//With m moles, heat capacity C at T and n moles of source at c J/K per mole and Ts it ends at
+//R * (m + n) * (C T + n c Ts) / ((C + n c) V), setting that to max_pressure is a quadratic in n
+fn mixed_room(
+    neighbour: &GasMixture,
+    source: &GasMixture,
+    max_pressure: f64,
+    gases: &GasRegistry,
+) -> f64 {
+    let source_moles = source.total_moles();
+    if source_moles <= 0.0 || source.temperature <= 0.0 {
+        return 0.0;
+    }
+    let limit = max_pressure * TILE_VOLUME / IDEAL_GAS_CONST;
+    let moles = neighbour.total_moles();
+    let heat_capacity = neighbour.heat_capacity(gases);
+    let per_mole = source.heat_capacity(gases) / source_moles;
+    let held = moles * neighbour.temperature;
+    if held >= limit || per_mole <= 0.0 {
+        return 0.0;
+    }
+
+    let a = per_mole * source.temperature;
+    let b = moles * a + heat_capacity * neighbour.temperature - limit * per_mole;
+    let c = heat_capacity * (held - limit);
+    let root = (b * b - 4.0 * a * c).sqrt();
+    //Positive root, written so neither form cancels
+    if b >= 0.0 {
+        2.0 * c / (-b - root)
+    } else {
+        (-b + root) / (2.0 * a)
+    }
+}
+
+/// Moves moles out of any tile above its max_pressure into open neighbours that have room below
+/// theirs, gas is only ever moved so the total moles are unchanged
+pub fn relieve_pressure<const N: usize>(
+    x: &mut [[GasMixture; N]; N],
+    wall_mask: &[[bool; N]; N],
+    max_pressure: &[[f64; N]; N],
+    gases: &GasRegistry,
+) {
+    for i in 0..N {
+        for j in 0..N {
+            let pressure = x[i][j].pressure(TILE_VOLUME);
+            if wall_mask[i][j] || pressure <= max_pressure[i][j] {
+                continue;
+            }
+            let source = x[i][j];
+            let excess = 1.0 - max_pressure[i][j] / pressure;
+            let source_moles = source.total_moles();
+            let excess_moles = source_moles * excess;
+
+            let mut room = Vec::with_capacity(4);
+            for (ni, nj) in neighbours(i, j, N) {
+                if !wall_mask[ni][nj] {
+                    let tile_room = mixed_room(&x[ni][nj], &source, max_pressure[ni][nj], gases);
+                    if tile_room > 0.0 {
+                        room.push((ni, nj, tile_room));
+                    }
+                }
+            }
+            let total_room: f64 = room.iter().map(|(_, _, room)| room).sum();
+            if total_room <= 0.0 {
+                continue;
+            }
+
+            let moved = excess_moles.min(total_room);
+            for (ni, nj, tile_room) in room {
+                let share = moved * tile_room / total_room;
+                x[ni][nj].add_fraction(&source, share / source_moles, gases);
+                for (amount, source) in x[i][j].amount.iter_mut().zip(source.amount.iter()) {
+                    *amount -= source * share / source_moles;
+                }
+            }
+        }
+    }
+}
+
+/// Tiles that gas on a tile is pushed into when it is walled over, the open neighbours or
+/// failing that the nearest open tiles through other walls placed at the same time.
+/// Empty when the tile is sealed in, older walls are never crossed
+pub fn displacement_targets<const N: usize>(
+    wall_mask: &[[bool; N]; N],
+    new_walls: &[(usize, usize)],
+    tile: (usize, usize),
+) -> Vec<(usize, usize)> {
+    let mut visited = vec![[false; N]; N];
+    visited[tile.0][tile.1] = true;
+    let mut frontier = vec![tile];
+    while !frontier.is_empty() {
+        let mut open = Vec::new();
+        let mut next = Vec::new();
+        for (i, j) in frontier {
+            for (ni, nj) in neighbours(i, j, N) {
+                if visited[ni][nj] {
+                    continue;
+                }
+                visited[ni][nj] = true;
+                if !wall_mask[ni][nj] {
+                    open.push((ni, nj));
+                } else if new_walls.contains(&(ni, nj)) {
+                    next.push((ni, nj));
+                }
+            }
+        }
+        if !open.is_empty() {
+            return open;
+        }
+        frontier = next;
+    }
+    Vec::new()
+}
+
+/// Splits the gas on a newly walled tile evenly between the targets and empties it, the tile
+/// keeps its temperature so heat still flows through the wall. With no targets the gas stays
+pub fn displace_gas(source: &mut GasMixture, targets: &mut [GasMixture], gases: &GasRegistry) {
+    if source.total_moles() <= 0.0 || targets.is_empty() {
+        return;
+    }
+    let fraction = 1.0 / targets.len() as f64;
+    for target in targets.iter_mut() {
+        target.add_fraction(source, fraction, gases);
+    }
+    *source = GasMixture {
+        temperature: source.temperature,
+        ..Default::default()
+    };
+}
+
+/// Takes up to wanted back out of the targets into tile, the reverse of displace_gas. Moles
+/// move at the temperature they are at and mix by heat capacity, as add_fraction does
+pub fn reclaim_gas(
+    tile: &mut GasMixture,
+    wanted: &GasMixture,
+    targets: &mut [GasMixture],
+    gases: &GasRegistry,
+) {
+    let mut heat = tile.heat_capacity(gases) * tile.temperature;
+    for (gas, desc) in gases.iter() {
+        let available: f64 = targets.iter().map(|target| target.amount[gas.0]).sum();
+        if available <= 0.0 {
+            continue;
+        }
+        let fraction = (wanted.amount[gas.0] / available).min(1.0);
+        for target in targets.iter_mut() {
+            let taken = target.amount[gas.0] * fraction;
+            target.amount[gas.0] -= taken;
+            tile.amount[gas.0] += taken;
+            heat += taken * desc.heat_capacity * target.temperature;
+        }
+    }
+    let heat_capacity = tile.heat_capacity(gases);
+    if heat_capacity > 0.0 {
+        tile.temperature = heat / heat_capacity;
+    }
+}
+
+//Runs f over the gas on tile and on the targets, then writes the targets back
+fn exchange_gas<const N: usize>(
+    tiles: &mut [[GasMixture; N]; N],
+    tile: (usize, usize),
+    targets: &[(usize, usize)],
+    f: impl FnOnce(&mut GasMixture, &mut [GasMixture]),
+) {
+    let mut mixtures: Vec<GasMixture> = targets.iter().map(|(i, j)| tiles[*i][*j]).collect();
+    f(&mut tiles[tile.0][tile.1], &mut mixtures);
+    for ((i, j), mixture) in targets.iter().zip(mixtures) {
+        tiles[*i][*j] = mixture;
+    }
+}
+
+/// Pushes the gas off each newly walled tile, wall_mask already has them set.
+/// Returns every tile it changed
+pub fn wall_over_gas<const N: usize>(
+    tiles: &mut [[GasMixture; N]; N],
+    wall_mask: &[[bool; N]; N],
+    walled: &[(usize, usize)],
+    gases: &GasRegistry,
+) -> Vec<(usize, usize)> {
+    let mut touched = Vec::new();
+    for &tile in walled {
+        let targets = displacement_targets(wall_mask, walled, tile);
+        exchange_gas(tiles, tile, &targets, |source, mixtures| {
+            displace_gas(source, mixtures, gases)
+        });
+        touched.push(tile);
+        touched.extend(targets);
+    }
+    touched
+}
+
+/// Takes gas back onto tile from wherever its wall pushed it as the wall comes down, the
+/// reverse of wall_over_gas. Returns every tile it changed
+pub fn unwall_gas<const N: usize>(
+    tiles: &mut [[GasMixture; N]; N],
+    wall_mask: &[[bool; N]; N],
+    tile: (usize, usize),
+    gas: &GasMixture,
+    gases: &GasRegistry,
+) -> Vec<(usize, usize)> {
+    let targets = displacement_targets(wall_mask, &[], tile);
+    exchange_gas(tiles, tile, &targets, |tile_gas, mixtures| {
+        reclaim_gas(tile_gas, gas, mixtures, gases)
+    });
+    let mut touched = targets;
+    touched.push(tile);
+    touched
+}
+
+/// Open tiles without a floor that can be reached from the edge of the grid without crossing
+/// a wall, anything enclosed or floored can hold gas
+pub fn space_mask<const N: usize>(
+    wall_mask: &[[bool; N]; N],
+    floor_mask: &[[bool; N]; N],
+) -> Box<[[bool; N]; N]> {
+    let mut reached: Box<[[bool; N]; N]> = new_boxed_grid(false);
+    let mut to_visit = Vec::new();
+    for k in 0..N {
+        for tile in [(k, 0), (k, N - 1), (0, k), (N - 1, k)] {
+            if !wall_mask[tile.0][tile.1] && !reached[tile.0][tile.1] {
+                reached[tile.0][tile.1] = true;
+                to_visit.push(tile);
+            }
+        }
+    }
+    while let Some((i, j)) = to_visit.pop() {
+        for (ni, nj) in neighbours(i, j, N) {
+            if !wall_mask[ni][nj] && !reached[ni][nj] {
+                reached[ni][nj] = true;
+                to_visit.push((ni, nj));
+            }
+        }
+    }
+
+    let mut space = new_boxed_grid(false);
+    for i in 0..N {
+        for j in 0..N {
+            space[i][j] = reached[i][j] && !floor_mask[i][j];
+        }
+    }
+    space
+}
+
+/// Empties the space tiles, whatever drifted onto them is gone
+pub fn vent_to_space<const N: usize>(x: &mut [[GasMixture; N]; N], space_mask: &[[bool; N]; N]) {
+    for (row, space_row) in x.iter_mut().zip(space_mask.iter()) {
+        for (tile, space) in row.iter_mut().zip(space_row.iter()) {
+            if *space {
+                *tile = GasMixture {
+                    temperature: SPACE_TEMPERATURE,
+                    ..Default::default()
+                };
+            }
+        }
+    }
+}
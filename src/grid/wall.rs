@@ -1,56 +1,41 @@
-use std::f32::consts::PI;
-
 use bevy::prelude::*;
+use bevy_inspector_egui::RegisterInspectable;
 
-use crate::{mouse::MousePosition, prelude::*};
+use crate::prelude::*;
 
-use super::{Wall, WallGrid, WallPlugin, GRID_SIZE};
+use super::floor::create_floor;
+use super::{
+    FloorGrid, FloorMaterial, Wall, WallEvent, WallGrid, WallPlugin, WallRepair, GRID_SIZE,
+};
 
 impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
         //Need grid to exist to populate it with air
         app.add_startup_system_to_stage(StartupStage::PostStartup, spawn_walls)
+            //Walls change in their own stage so the grid and the entities are spawned together
+            .add_stage_after(CoreStage::Update, "walls", SystemStage::parallel())
+            .add_event::<WallEvent>()
+            .add_system_to_stage("walls", validate_walls.label("validate_walls"))
+            .add_system_to_stage("walls", apply_wall_events.after("validate_walls"))
             .add_system_to_stage(CoreStage::PostUpdate, wall_update)
-            .add_system(mouse_create_wall);
+            .add_system_to_stage(CoreStage::PostUpdate, wall_material_update)
+            .register_inspectable::<Wall>();
     }
 }
 
-fn mouse_create_wall(
-    mut commands: Commands,
-    mut wall_query: Query<(Entity, &GlobalTransform, &mut WallGrid)>,
-    mouse: Res<MousePosition>,
-    mouse_input: Res<Input<MouseButton>>,
-) {
-    let (ent, transform, mut grid) = wall_query.iter_mut().next().unwrap();
-    let target = get_mouse_tile(mouse.0, transform.translation.truncate(), grid.tile_size);
-
-    if mouse_input.pressed(MouseButton::Left) {
-        let new_wall = create_wall(&mut commands, &mut grid, target.0, target.1);
-        if let Some(new_wall) = new_wall {
-            commands.entity(ent).add_child(new_wall);
-        }
-    }
-
-    if mouse_input.pressed(MouseButton::Right) {
-        if let Some(wall) = grid.walls[target.0][target.1] {
-            commands.entity(wall).despawn_recursive();
-            grid.walls[target.0][target.1] = None;
-        }
-    }
-}
-
-fn get_mouse_tile(position: Vec2, map_pos: Vec2, tile_size: f32) -> (usize, usize) {
-    let x = (position.x - map_pos.x + tile_size * 0.5) / tile_size;
-    let y = (position.y - map_pos.y + tile_size * 0.5) / tile_size;
-    (x as usize, y as usize)
-}
-
-//XXX creates updates grid before entity is actually spawned...
-fn create_wall(commands: &mut Commands, grid: &mut WallGrid, x: usize, y: usize) -> Option<Entity> {
-    if grid.walls[x][y] == None {
+//Only safe where nothing reads the grid until commands are applied, startup or the walls stage
+fn create_wall(
+    commands: &mut Commands,
+    grid: &mut WallGrid,
+    x: usize,
+    y: usize,
+    material: WallMaterial,
+) -> Option<Entity> {
+    if grid.walls[x][y].is_none() {
         let wall = commands
             .spawn()
             .insert(Graphic::WorldObject(WorldObject::Wall(
+                material,
                 WallConnection::None,
             )))
             //FIXME assumes wall size is 32
@@ -59,7 +44,8 @@ fn create_wall(commands: &mut Commands, grid: &mut WallGrid, x: usize, y: usize)
                 y as f32 * grid.tile_size,
                 0.0,
             ))
-            .insert(Wall)
+            .insert(Wall { material })
+            .insert(TileOrientation::default())
             .insert(Name::new("Wall"))
             .id();
         grid.walls[x][y] = Some(wall);
@@ -68,18 +54,116 @@ fn create_wall(commands: &mut Commands, grid: &mut WallGrid, x: usize, y: usize)
     None
 }
 
+fn apply_wall_events(
+    mut commands: Commands,
+    mut events: EventReader<WallEvent>,
+    mut grid_query: Query<(Entity, &mut WallGrid)>,
+) {
+    let (grid_ent, mut grid) = grid_query.single_mut();
+    for event in events.iter() {
+        match *event {
+            WallEvent::Place { tile, material } => {
+                match create_wall(&mut commands, &mut grid, tile.0, tile.1, material) {
+                    Some(wall) => {
+                        commands.entity(grid_ent).add_child(wall);
+                    }
+                    None => warn!("Tile {:?} already has a wall", tile),
+                }
+            }
+            WallEvent::Remove { tile } => match grid.walls[tile.0][tile.1].take() {
+                Some(wall) => commands.entity(wall).despawn_recursive(),
+                None => warn!("Tile {:?} has no wall to remove", tile),
+            },
+        }
+    }
+}
+
+/// Repairs that make the grid match the walls, given each Wall entity and the tile it sits on
+pub fn wall_repairs(
+    grid: &[[Option<Entity>; GRID_SIZE]; GRID_SIZE],
+    walls: &[(Entity, Option<(usize, usize)>)],
+) -> Vec<WallRepair> {
+    let mut repairs = Vec::new();
+    let mut expected = [[None; GRID_SIZE]; GRID_SIZE];
+    for (ent, tile) in walls.iter() {
+        if let Some((x, y)) = tile {
+            if grid[*x][*y] == Some(*ent) {
+                expected[*x][*y] = Some(*ent);
+            }
+        }
+    }
+    for (x, row) in grid.iter().enumerate() {
+        for (y, ent) in row.iter().enumerate() {
+            if ent.is_some() && expected[x][y].is_none() {
+                repairs.push(WallRepair::Clear((x, y)));
+            }
+        }
+    }
+    for (ent, tile) in walls.iter() {
+        match tile {
+            Some((x, y)) if expected[*x][*y] == Some(*ent) => {}
+            Some((x, y)) if expected[*x][*y].is_none() => {
+                expected[*x][*y] = Some(*ent);
+                repairs.push(WallRepair::Insert((*x, *y), *ent));
+            }
+            _ => repairs.push(WallRepair::Despawn(*ent)),
+        }
+    }
+    repairs
+}
+
+fn validate_walls(
+    mut commands: Commands,
+    mut grid_query: Query<(Entity, &mut WallGrid)>,
+    wall_query: Query<(Entity, &Transform), With<Wall>>,
+) {
+    let (grid_ent, grid) = grid_query.single();
+    let walls: Vec<(Entity, Option<(usize, usize)>)> = wall_query
+        .iter()
+        .map(|(ent, transform)| {
+            //Walls are children of the grid so this is already grid space
+            let tile = (transform.translation.truncate() / grid.tile_size).round();
+            let in_grid =
+                tile.cmpge(Vec2::ZERO).all() && tile.cmplt(Vec2::splat(GRID_SIZE as f32)).all();
+            (ent, in_grid.then_some((tile.x as usize, tile.y as usize)))
+        })
+        .collect();
+    let repairs = wall_repairs(&grid.walls, &walls);
+    if repairs.is_empty() {
+        return;
+    }
+
+    let (_, mut grid) = grid_query.single_mut();
+    for repair in repairs {
+        warn!("Wall grid out of sync, {:?}", repair);
+        match repair {
+            WallRepair::Clear((x, y)) => grid.walls[x][y] = None,
+            WallRepair::Insert((x, y), ent) => {
+                grid.walls[x][y] = Some(ent);
+                commands.entity(grid_ent).add_child(ent);
+            }
+            WallRepair::Despawn(ent) => commands.entity(ent).despawn_recursive(),
+        }
+    }
+}
+
 fn create_room(
     commands: &mut Commands,
     grid: &mut WallGrid,
+    floors: &mut FloorGrid,
+    floor: FloorMaterial,
     x_offset: usize,
     y_offset: usize,
     width: usize,
     height: usize,
+    material: WallMaterial,
 
     tile_query: &mut Query<&mut GasMixture>,
     grid_query: &Query<&GasGrid>,
+    gases: &GasRegistry,
 ) {
     let gas_grid = grid_query.single();
+    let oxygen = gases.get("Oxygen").expect("Oxygen missing from gases.ron");
     println!("Creating room");
     for x in 1..(width - 1) {
         for y in 1..(height - 1) {
@@ -87,73 +171,133 @@ fn create_room(
                 .get_mut(gas_grid.grid[x + x_offset][y + y_offset])
                 .unwrap();
             tile.temperature = 293.0;
-            tile.amount[Gas::Oxygen as usize] = 83.0;
+            tile.amount[oxygen.0] = 83.0;
         }
     }
 
-    let y1 = 0;
-    let y2 = height - 1;
-    for x in 0..width {
-        create_wall(commands, grid, x + x_offset, y1 + y_offset);
-        create_wall(commands, grid, x + x_offset, y2 + y_offset);
+    let corner = (x_offset + width - 1, y_offset + height - 1);
+    for (x, y) in rectangle_tiles((x_offset, y_offset), corner, false) {
+        create_wall(commands, grid, x, y, material);
+    }
+    //Floor runs under the walls too
+    for (x, y) in rectangle_tiles((x_offset, y_offset), corner, true) {
+        create_floor(commands, floors, grid.tile_size, x, y, floor);
     }
+}
 
-    let x1 = 0;
-    let x2 = width - 1;
-    for y in 1..(height - 1) {
-        create_wall(commands, grid, x1 + x_offset, y + y_offset);
-        create_wall(commands, grid, x2 + x_offset, y + y_offset);
+/// Tiles on the rectangle with corners a and b, just its border unless filled
+pub fn rectangle_tiles(a: (usize, usize), b: (usize, usize), filled: bool) -> Vec<(usize, usize)> {
+    let (x1, x2) = (a.0.min(b.0), a.0.max(b.0));
+    let (y1, y2) = (a.1.min(b.1), a.1.max(b.1));
+    let mut tiles = Vec::new();
+    for x in x1..=x2 {
+        for y in y1..=y2 {
+            if filled || x == x1 || x == x2 || y == y1 || y == y2 {
+                tiles.push((x, y));
+            }
+        }
     }
+    tiles
+}
+
+/// Tiles on the line from a to b (Bresenham), diagonal steps go through a side tile so walls still seal
+pub fn line_tiles(a: (usize, usize), b: (usize, usize)) -> Vec<(usize, usize)> {
+    let (mut x, mut y) = (a.0 as isize, a.1 as isize);
+    let (x2, y2) = (b.0 as isize, b.1 as isize);
+    let dx = (x2 - x).abs();
+    let dy = -(y2 - y).abs();
+    let step_x = if x < x2 { 1 } else { -1 };
+    let step_y = if y < y2 { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut tiles = vec![(x as usize, y as usize)];
+    while (x, y) != (x2, y2) {
+        let doubled = 2 * error;
+        let move_x = doubled >= dy;
+        let move_y = doubled <= dx;
+        if move_x {
+            error += dy;
+            x += step_x;
+        }
+        if move_y {
+            if move_x {
+                tiles.push((x as usize, y as usize));
+            }
+            error += dx;
+            y += step_y;
+        }
+        tiles.push((x as usize, y as usize));
+    }
+    tiles
 }
 
 fn spawn_walls(
     mut commands: Commands,
     mut tile_query: Query<&mut GasMixture>,
     grid_query: Query<&GasGrid>,
+    gases: Res<GasRegistry>,
 ) {
     let mut grid = WallGrid {
         tile_size: 32.0,
         walls: [[None; GRID_SIZE]; GRID_SIZE],
     };
+    let mut floors = FloorGrid {
+        floors: [[None; GRID_SIZE]; GRID_SIZE],
+    };
     create_room(
         &mut commands,
         &mut grid,
+        &mut floors,
+        FloorMaterial::Plating,
         22,
         22,
         6,
         6,
+        WallMaterial::Metal,
         &mut tile_query,
         &grid_query,
+        &gases,
     );
     create_room(
         &mut commands,
         &mut grid,
+        &mut floors,
+        FloorMaterial::Grating,
         20,
         27,
         3,
         8,
+        WallMaterial::Glass,
         &mut tile_query,
         &grid_query,
+        &gases,
     );
     create_room(
         &mut commands,
         &mut grid,
+        &mut floors,
+        FloorMaterial::Plating,
         27,
         23,
         8,
         4,
+        WallMaterial::Reinforced,
         &mut tile_query,
         &grid_query,
+        &gases,
     );
     create_room(
         &mut commands,
         &mut grid,
+        &mut floors,
+        FloorMaterial::Carpet,
         17,
         22,
         6,
         6,
+        WallMaterial::Insulated,
         &mut tile_query,
         &grid_query,
+        &gases,
     );
 
     let mut to_add: Vec<Entity> = Vec::new();
@@ -161,6 +305,7 @@ fn spawn_walls(
     for ent in grid.walls.iter().flatten().flatten() {
         to_add.push(*ent);
     }
+    to_add.extend(floors.floors.iter().flatten().flatten());
 
     commands
         .spawn()
@@ -172,124 +317,85 @@ fn spawn_walls(
             100.0,
         ))
         .insert(grid)
+        .insert(floors)
         .insert(Name::new("Wall Grid"));
 }
 
-//TODO if a wall entity is not in grid warn!
-//XXX Becareful making things children of walls as this rotates them...
 fn wall_update(
-    mut wall_query: Query<(&mut Graphic, &mut Transform), With<Wall>>,
+    mut wall_query: Query<(&mut Graphic, &mut TileOrientation, &Wall)>,
     grid_query: Query<&WallGrid, Changed<WallGrid>>,
+    rules: Res<AutotileRules>,
 ) {
     for grid in grid_query.iter() {
         for (i, row) in grid.walls.iter().enumerate() {
             for (j, ent) in row.iter().enumerate() {
                 if let Some(ent) = ent {
-                    let left = i > 0 && grid.walls[i - 1][j].is_some();
-                    let right = i < GRID_SIZE - 1 && grid.walls[i + 1][j].is_some();
-                    let down = j > 0 && grid.walls[i][j - 1].is_some();
-                    let up = j < GRID_SIZE - 1 && grid.walls[i][j + 1].is_some();
-
-                    if let Ok((mut graphic, mut transform)) = wall_query.get_mut(*ent) {
-                        match (left, right, up, down) {
-                            (false, false, false, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::None));
-                                transform.rotation = Quat::from_rotation_z(0.0);
-                            }
-                            //Down
-                            (false, false, false, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::One));
-                                transform.rotation = Quat::from_rotation_z(-90.0 * PI / 180.0);
-                            }
-                            //Up
-                            (false, false, true, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::One));
-                                transform.rotation = Quat::from_rotation_z(90.0 * PI / 180.0);
-                            }
-                            //Up Down
-                            (false, false, true, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::Cross));
-                                transform.rotation = Quat::from_rotation_z(90.0 * PI / 180.0);
-                            }
-                            //Right
-                            (false, true, false, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::One));
-                                transform.rotation = Quat::from_rotation_z(0.0 * PI / 180.0);
-                            }
-                            //Down right
-                            (false, true, false, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::Corner));
-                                transform.rotation = Quat::from_rotation_z(0.0 * PI / 180.0);
-                            }
-                            //Up right
-                            (false, true, true, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::Corner));
-                                transform.rotation = Quat::from_rotation_z(90.0 * PI / 180.0);
-                            }
-                            //Up down right
-                            (false, true, true, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::T));
-                                transform.rotation = Quat::from_rotation_z(90.0 * PI / 180.0);
-                            }
-                            //Left
-                            (true, false, false, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::One));
-                                transform.rotation = Quat::from_rotation_z(180.0 * PI / 180.0);
-                            }
-                            //Left Down
-                            (true, false, false, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::Corner));
-                                transform.rotation = Quat::from_rotation_z(-90.0 * PI / 180.0);
-                            }
-                            //left up
-                            (true, false, true, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::Corner));
-                                transform.rotation = Quat::from_rotation_z(180.0 * PI / 180.0);
-                            }
-                            //left up down
-                            (true, false, true, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::T));
-                                transform.rotation = Quat::from_rotation_z(-90.0 * PI / 180.0);
-                            }
-                            //left right
-                            (true, true, false, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::Cross));
-                                transform.rotation = Quat::from_rotation_z(0.0 * PI / 180.0);
-                            }
-                            //left right down
-                            (true, true, false, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::T));
-                                transform.rotation = Quat::from_rotation_z(0.0 * PI / 180.0);
-                            }
-                            //left right up
-                            (true, true, true, false) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::T));
-                                transform.rotation = Quat::from_rotation_z(180.0 * PI / 180.0);
-                            }
-                            (true, true, true, true) => {
-                                *graphic =
-                                    Graphic::WorldObject(WorldObject::Wall(WallConnection::All));
-                                transform.rotation = Quat::from_rotation_z(0.0 * PI / 180.0);
-                            }
-                        }
+                    if let Ok((mut graphic, mut orientation, wall)) = wall_query.get_mut(*ent) {
+                        let rule = autotile(&rules, &grid.walls, i, j);
+                        *graphic =
+                            Graphic::WorldObject(WorldObject::Wall(wall.material, rule.connection));
+                        *orientation = rule.orientation;
                     }
                 }
             }
         }
     }
 }
+
+/// Neighbours of tile i, j that are filled as bits clockwise from up, see graphics_desc.ron,
+/// corners are only set when both sides next to them are so blob tiles stay connected
+pub fn autotile_mask(tiles: &[[Option<Entity>; GRID_SIZE]; GRID_SIZE], i: usize, j: usize) -> u8 {
+    let filled = |di: isize, dj: isize| {
+        let (x, y) = (i as isize + di, j as isize + dj);
+        x >= 0
+            && y >= 0
+            && x < GRID_SIZE as isize
+            && y < GRID_SIZE as isize
+            && tiles[x as usize][y as usize].is_some()
+    };
+    let up = filled(0, 1);
+    let right = filled(1, 0);
+    let down = filled(0, -1);
+    let left = filled(-1, 0);
+    [
+        up,
+        up && right && filled(1, 1),
+        right,
+        down && right && filled(1, -1),
+        down,
+        down && left && filled(-1, -1),
+        left,
+        up && left && filled(-1, 1),
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |mask, (bit, set)| mask | ((*set as u8) << bit))
+}
+
+/// Connection sprite and orientation for tile i, j based on which neighbours are filled
+pub fn autotile(
+    rules: &AutotileRules,
+    tiles: &[[Option<Entity>; GRID_SIZE]; GRID_SIZE],
+    i: usize,
+    j: usize,
+) -> AutotileRule {
+    rules.rule(autotile_mask(tiles, i, j))
+}
+
+/// Same as autotile from the sides only, for pipes and wires which have no blob sprites
+pub fn line_autotile(
+    rules: &AutotileRules,
+    tiles: &[[Option<Entity>; GRID_SIZE]; GRID_SIZE],
+    i: usize,
+    j: usize,
+) -> AutotileRule {
+    rules.rule(autotile_mask(tiles, i, j) & AutotileRules::SIDES)
+}
+
+fn wall_material_update(mut wall_query: Query<(&mut Graphic, &Wall), Changed<Wall>>) {
+    for (mut graphic, wall) in wall_query.iter_mut() {
+        if let Graphic::WorldObject(WorldObject::Wall(_, connection)) = *graphic {
+            *graphic = Graphic::WorldObject(WorldObject::Wall(wall.material, connection));
+        }
+    }
+}
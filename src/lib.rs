@@ -0,0 +1,12 @@
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::type_complexity)]
+#![allow(clippy::too_many_arguments)]
+
+pub mod build;
+pub mod debug;
+pub mod graphics;
+pub mod grid;
+pub mod machines;
+pub mod mouse;
+pub mod player;
+pub mod prelude;
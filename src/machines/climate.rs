@@ -0,0 +1,161 @@
+use bevy::utils::HashSet;
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::ClimatePlugin;
+
+impl Plugin for ClimatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_climate_machines)
+            .add_system(heat_tiles)
+            .add_system(thermostat_switch)
+            .add_system(update_climate_graphics)
+            .register_inspectable::<Heater>()
+            .register_inspectable::<Cooler>()
+            .register_inspectable::<Thermostat>();
+    }
+}
+
+pub fn spawn_heater(commands: &mut Commands, position: Vec3) -> Entity {
+    let heater = comp_from_config!(Heater);
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Heater(heater.on)))
+        .insert(heater)
+        .insert(PowerConsumer::default())
+        .insert(Name::new("Heater"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+pub fn spawn_cooler(commands: &mut Commands, position: Vec3) -> Entity {
+    let cooler = comp_from_config!(Cooler);
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Cooler(cooler.on)))
+        .insert(cooler)
+        .insert(PowerConsumer::default())
+        .insert(Name::new("Cooler"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+pub fn spawn_thermostat(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Thermostat))
+        .insert(comp_from_config!(Thermostat))
+        .insert(Name::new("Thermostat"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+//Keeps the insulated room warm
+fn spawn_climate_machines(mut commands: Commands) {
+    spawn_heater(&mut commands, Vec3::new(-160.0, -32.0, 100.0));
+    spawn_cooler(&mut commands, Vec3::new(-128.0, 0.0, 100.0));
+    spawn_thermostat(&mut commands, Vec3::new(-224.0, -32.0, 100.0));
+}
+
+//Heaters and coolers only draw power while on
+fn heat_tiles(
+    mut heater_query: Query<(&GlobalTransform, &Heater, &mut PowerConsumer), Without<Cooler>>,
+    mut cooler_query: Query<(&GlobalTransform, &Cooler, &mut PowerConsumer)>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    mut tile_query: Query<&mut GasMixture>,
+    gases: Res<GasRegistry>,
+    time: Res<Time>,
+) {
+    let (grid, grid_transform) = grid_query.single();
+    let heaters = heater_query
+        .iter_mut()
+        .map(|(transform, heater, consumer)| (transform, heater.on, heater.watts, consumer));
+    let coolers = cooler_query
+        .iter_mut()
+        .map(|(transform, cooler, consumer)| (transform, cooler.on, -cooler.watts, consumer));
+
+    for (transform, on, watts, mut consumer) in heaters.chain(coolers) {
+        let draw = if on { watts.abs() } else { 0.0 };
+        if consumer.watts != draw {
+            consumer.watts = draw;
+        }
+        if !on {
+            continue;
+        }
+        if let Some((i, j)) = world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        ) {
+            let mut tile = tile_query.get_mut(grid.grid[i][j]).unwrap();
+            tile.add_heat(
+                watts * consumer.supply * time.delta_seconds() as f64,
+                &gases,
+            );
+        }
+    }
+}
+
+fn thermostat_switch(
+    thermostat_query: Query<(&GlobalTransform, &Thermostat)>,
+    mut heater_query: Query<(&GlobalTransform, &mut Heater)>,
+    mut cooler_query: Query<(&GlobalTransform, &mut Cooler)>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    tile_query: Query<&GasMixture>,
+) {
+    let (grid, grid_transform) = grid_query.single();
+    let to_tile = |transform: &GlobalTransform| {
+        world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        )
+    };
+
+    for (transform, thermostat) in thermostat_query.iter() {
+        let tile = match to_tile(transform) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let temperature = tile_query
+            .get(grid.grid[tile.0][tile.1])
+            .unwrap()
+            .temperature;
+        let room: HashSet<(usize, usize)> = grid.room(tile).into_iter().collect();
+
+        for (transform, mut heater) in heater_query.iter_mut() {
+            if to_tile(transform).is_some_and(|tile| room.contains(&tile)) {
+                if temperature < thermostat.target - thermostat.hysteresis && !heater.on {
+                    heater.on = true;
+                } else if temperature >= thermostat.target && heater.on {
+                    heater.on = false;
+                }
+            }
+        }
+        for (transform, mut cooler) in cooler_query.iter_mut() {
+            if to_tile(transform).is_some_and(|tile| room.contains(&tile)) {
+                if temperature > thermostat.target + thermostat.hysteresis && !cooler.on {
+                    cooler.on = true;
+                } else if temperature <= thermostat.target && cooler.on {
+                    cooler.on = false;
+                }
+            }
+        }
+    }
+}
+
+fn update_climate_graphics(
+    mut heater_query: Query<(&Heater, &mut Graphic), (Changed<Heater>, Without<Cooler>)>,
+    mut cooler_query: Query<(&Cooler, &mut Graphic), Changed<Cooler>>,
+) {
+    for (heater, mut graphic) in heater_query.iter_mut() {
+        *graphic = Graphic::WorldObject(WorldObject::Heater(heater.on));
+    }
+    for (cooler, mut graphic) in cooler_query.iter_mut() {
+        *graphic = Graphic::WorldObject(WorldObject::Cooler(cooler.on));
+    }
+}
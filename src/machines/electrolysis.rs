@@ -0,0 +1,86 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::ElectrolysisPlugin;
+
+impl Plugin for ElectrolysisPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_electrolysis_machines)
+            .add_system(electrolyze)
+            .add_system(update_electrolyzer_graphics)
+            .register_inspectable::<Electrolyzer>();
+    }
+}
+
+pub fn spawn_electrolyzer(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Electrolyzer(false)))
+        .insert(comp_from_config!(Electrolyzer))
+        .insert(PowerConsumer::default())
+        .insert(Name::new("Electrolyzer"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+fn spawn_electrolysis_machines(mut commands: Commands) {
+    spawn_electrolyzer(&mut commands, Vec3::new(256.0, 0.0, 100.0));
+}
+
+//2 H2O -> 2 H2 + O2, only draws power while there is water on its tile
+fn electrolyze(
+    mut electrolyzer_query: Query<(&GlobalTransform, &mut Electrolyzer, &mut PowerConsumer)>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    mut tile_query: Query<&mut GasMixture>,
+    gases: Res<GasRegistry>,
+    time: Res<Time>,
+) {
+    let (grid, grid_transform) = grid_query.single();
+    let water = gases
+        .get("WaterVapor")
+        .expect("WaterVapor missing from gases.ron");
+    let hydrogen = gases
+        .get("Hydrogen")
+        .expect("Hydrogen missing from gases.ron");
+    let oxygen = gases.get("Oxygen").expect("Oxygen missing from gases.ron");
+
+    for (transform, mut electrolyzer, mut consumer) in electrolyzer_query.iter_mut() {
+        let (i, j) = match world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        ) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let mut tile = tile_query.get_mut(grid.grid[i][j]).unwrap();
+
+        let has_water = tile.amount[water.0] > 0.0;
+        let watts = if has_water { electrolyzer.power } else { 0.0 };
+        if consumer.watts != watts {
+            consumer.watts = watts;
+        }
+
+        let active = has_water && consumer.supply > 0.0;
+        if active {
+            let split = tile.amount[water.0]
+                .min(electrolyzer.rate * consumer.supply * time.delta_seconds() as f64);
+            tile.amount[water.0] -= split;
+            tile.amount[hydrogen.0] += split;
+            tile.amount[oxygen.0] += split / 2.0;
+        }
+        if electrolyzer.active != active {
+            electrolyzer.active = active;
+        }
+    }
+}
+
+fn update_electrolyzer_graphics(
+    mut electrolyzer_query: Query<(&Electrolyzer, &mut Graphic), Changed<Electrolyzer>>,
+) {
+    for (electrolyzer, mut graphic) in electrolyzer_query.iter_mut() {
+        *graphic = Graphic::WorldObject(WorldObject::Electrolyzer(electrolyzer.active));
+    }
+}
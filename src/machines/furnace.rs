@@ -0,0 +1,137 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::FurnacePlugin;
+
+impl Plugin for FurnacePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_furnaces)
+            .add_system(burn_furnaces)
+            .add_system(light_furnace)
+            .add_system(update_furnace_graphics)
+            .register_inspectable::<Furnace>();
+    }
+}
+
+impl Furnace {
+    /// Burns dt seconds of fuel with the oxygen in tile, goes out when the fuel or oxygen runs low
+    pub fn burn(&mut self, tile: &mut GasMixture, oxygen: Gas, carbon_dioxide: Gas, dt: f64) {
+        if !self.burning {
+            return;
+        }
+        if self.fuel <= 0.0 || tile.get_pressure(oxygen) < self.min_oxygen_pressure {
+            self.burning = false;
+            return;
+        }
+        //Burns slower if the tile cannot give it all the oxygen it wants
+        let wanted = (self.burn_rate * dt).min(self.fuel);
+        let burnt = wanted.min(tile.amount[oxygen.0] / self.oxygen_per_kg);
+        let moles = burnt * self.oxygen_per_kg;
+        self.fuel -= burnt;
+        tile.amount[oxygen.0] -= moles;
+        tile.amount[carbon_dioxide.0] += moles;
+        self.temperature += burnt * self.energy_density / self.heat_capacity;
+    }
+}
+
+pub fn spawn_furnace(commands: &mut Commands, position: Vec3, assets: &AssetServer) -> Entity {
+    let furnace = comp_from_config!(Furnace);
+    //Spawners are children so they follow the furnace, particles are drawn over the gas overlay
+    let fire = spawn_particle_spawner(
+        commands,
+        "config/fire_particle_spawner.ron",
+        Vec3::new(-10.0, 9.5, 800.0),
+        assets,
+    );
+    let smoke = spawn_particle_spawner(
+        commands,
+        "config/smoke_particle_spawner.ron",
+        Vec3::new(-10.0, 20.0, 801.0),
+        assets,
+    );
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Furance(furnace.burning)))
+        .insert(furnace)
+        .insert(Name::new("Furnace"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .push_children(&[fire, smoke])
+        .id()
+}
+
+fn spawn_furnaces(mut commands: Commands, assets: Res<AssetServer>) {
+    spawn_furnace(&mut commands, Vec3::new(-220.0, 32.0, 100.0), &assets);
+}
+
+//Clicking relights a furnace or puts it out
+fn light_furnace(
+    mut furnace_query: Query<&mut Furnace>,
+    mut clicks: EventReader<EntityClicked>,
+    build: Res<BuildMode>,
+) {
+    for click in clicks.iter() {
+        if click.button != ClickButton::Left || build.active {
+            continue;
+        }
+        if let Ok(mut furnace) = furnace_query.get_mut(click.entity) {
+            furnace.burning = !furnace.burning && furnace.fuel > 0.0;
+        }
+    }
+}
+
+fn burn_furnaces(
+    mut furnace_query: Query<(&GlobalTransform, &mut Furnace)>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    mut tile_query: Query<&mut GasMixture>,
+    gases: Res<GasRegistry>,
+    time: Res<Time>,
+) {
+    let (grid, grid_transform) = grid_query.single();
+    let oxygen = gases.get("Oxygen").expect("Oxygen missing from gases.ron");
+    let carbon_dioxide = gases
+        .get("CarbonDioxide")
+        .expect("CarbonDioxide missing from gases.ron");
+    let dt = time.delta_seconds() as f64;
+
+    for (transform, mut furnace) in furnace_query.iter_mut() {
+        let (i, j) = match world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        ) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let mut tile = tile_query.get_mut(grid.grid[i][j]).unwrap();
+
+        furnace.burn(&mut tile, oxygen, carbon_dioxide, dt);
+
+        //Hot furnaces keep warming the tile after going out
+        let joules = furnace.conductance * (furnace.temperature - tile.temperature) * dt;
+        if tile.heat_capacity(&gases) > 0.0 {
+            furnace.temperature -= joules / furnace.heat_capacity;
+            tile.add_heat(joules, &gases);
+        }
+    }
+}
+
+fn update_furnace_graphics(
+    mut furnace_query: Query<(&Furnace, &mut Graphic, &Children), Changed<Furnace>>,
+    mut spawner_query: Query<&mut ParticleSpawner>,
+) {
+    for (furnace, mut graphic, children) in furnace_query.iter_mut() {
+        let burning = Graphic::WorldObject(WorldObject::Furance(furnace.burning));
+        if *graphic != burning {
+            *graphic = burning;
+        }
+        for child in children.iter() {
+            if let Ok(mut spawner) = spawner_query.get_mut(*child) {
+                if spawner.paused == furnace.burning {
+                    spawner.paused = !furnace.burning;
+                }
+            }
+        }
+    }
+}
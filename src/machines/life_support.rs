@@ -0,0 +1,262 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::LifeSupportPlugin;
+
+//Format to be loaded from ron
+#[derive(Deserialize)]
+struct LifeSupportDesc {
+    supply: HashMap<String, f64>,
+    temperature: f64,
+}
+
+impl Plugin for LifeSupportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_life_support)
+            .add_startup_system(spawn_life_support)
+            .add_system(vent_output)
+            .add_system(scrubber_intake)
+            .add_system(life_support_overlay)
+            .register_inspectable::<Vent>()
+            .register_inspectable::<Scrubber>();
+    }
+}
+
+fn setup_life_support(mut commands: Commands, gases: Res<GasRegistry>) {
+    let desc = comp_from_config!(LifeSupportDesc, "config/life_support.ron");
+    let mut supply = GasMixture {
+        temperature: desc.temperature,
+        ..Default::default()
+    };
+    for (name, amount) in desc.supply.iter() {
+        match gases.get(name) {
+            Some(gas) => supply.amount[gas.0] = *amount,
+            None => error!("Unknown gas {} in life_support.ron", name),
+        }
+    }
+    commands.insert_resource(LifeSupportNetwork {
+        supply,
+        storage: GasMixture {
+            temperature: desc.temperature,
+            ..Default::default()
+        },
+    });
+}
+
+pub fn spawn_vent(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Vent))
+        .insert(comp_from_config!(Vent))
+        .insert(PowerConsumer::default())
+        .insert(Name::new("Vent"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+pub fn spawn_scrubber(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Scrubber))
+        .insert(comp_from_config!(Scrubber))
+        .insert(PowerConsumer::default())
+        .insert(Name::new("Scrubber"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+//Keeps the starting room breathable
+fn spawn_life_support(mut commands: Commands) {
+    //On the connector at the end of the starting pipe run
+    spawn_vent(&mut commands, Vec3::new(-32.0, -64.0, 100.0));
+    spawn_scrubber(&mut commands, Vec3::new(-64.0, 0.0, 100.0));
+}
+
+fn vent_output(
+    mut vent_query: Query<(&GlobalTransform, &mut Vent, &mut PowerConsumer)>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    mut tile_query: Query<&mut GasMixture, Without<Pipe>>,
+    pipe_grid_query: Query<&PipeGrid>,
+    mut pipe_query: Query<&mut GasMixture, With<Pipe>>,
+    connector_query: Query<&GlobalTransform, With<PipeConnector>>,
+    mut network: ResMut<LifeSupportNetwork>,
+    gases: Res<GasRegistry>,
+    time: Res<Time>,
+) {
+    let (grid, grid_transform) = grid_query.single();
+    let pipe_grid = pipe_grid_query.single();
+    let tile_of = |transform: &GlobalTransform| {
+        world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        )
+    };
+    for (transform, mut vent, mut consumer) in vent_query.iter_mut() {
+        if consumer.watts != vent.power {
+            consumer.watts = vent.power;
+        }
+        let (i, j) = match tile_of(transform) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let mut tile = tile_query.get_mut(grid.grid[i][j]).unwrap();
+        let rate = vent.rate * consumer.supply * time.delta_seconds() as f64;
+
+        //Pipe grid lines up with the gas grid, a vent on a connector is fed by the pipe instead
+        let connected = connector_query
+            .iter()
+            .any(|connector| tile_of(connector) == Some((i, j)));
+        let pipe = pipe_grid.pipes[i][j].filter(|_| connected);
+        let active = match pipe.and_then(|ent| pipe_query.get_mut(ent).ok()) {
+            Some(mut pipe) => {
+                let before = tile.total_moles();
+                pump_gas(
+                    &mut pipe,
+                    &mut tile,
+                    TILE_VOLUME,
+                    rate,
+                    vent.target_pressure,
+                    &gases,
+                );
+                tile.total_moles() > before
+            }
+            None => {
+                //Moles needed to reach the target if they came in at the supply temperature
+                let missing = (vent.target_pressure - tile.get_total_pressure() as f64)
+                    * TILE_VOLUME
+                    / (IDEAL_GAS_CONST * network.supply.temperature);
+                let to_add = missing.min(rate);
+
+                let mut output = GasMixture {
+                    temperature: network.supply.temperature,
+                    ..Default::default()
+                };
+                if to_add > 0.0 {
+                    for share in vent.mix.iter() {
+                        if let Some(gas) = gases.get(&share.gas) {
+                            let amount =
+                                (to_add * share.fraction).min(network.supply.amount[gas.0]);
+                            network.supply.amount[gas.0] -= amount;
+                            output.amount[gas.0] += amount;
+                        }
+                    }
+                }
+                let active = output.total_moles() > 0.0;
+                if active {
+                    tile.add_fraction(&output, 1.0, &gases);
+                }
+                active
+            }
+        };
+        if vent.active != active {
+            vent.active = active;
+        }
+    }
+}
+
+fn scrubber_intake(
+    mut scrubber_query: Query<(&GlobalTransform, &mut Scrubber, &mut PowerConsumer)>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    mut tile_query: Query<&mut GasMixture>,
+    mut network: ResMut<LifeSupportNetwork>,
+    gases: Res<GasRegistry>,
+    time: Res<Time>,
+) {
+    let (grid, grid_transform) = grid_query.single();
+    for (transform, mut scrubber, mut consumer) in scrubber_query.iter_mut() {
+        if consumer.watts != scrubber.power {
+            consumer.watts = scrubber.power;
+        }
+        let (i, j) = match world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        ) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let mut tile = tile_query.get_mut(grid.grid[i][j]).unwrap();
+
+        let fraction = (scrubber.rate * consumer.supply * time.delta_seconds() as f64).min(1.0);
+        let mut removed = 0.0;
+        for name in scrubber.filter.iter() {
+            if let Some(gas) = gases.get(name) {
+                let amount = tile.amount[gas.0] * fraction;
+                tile.amount[gas.0] -= amount;
+                network.storage.amount[gas.0] += amount;
+                removed += amount;
+            }
+        }
+
+        //Ignore the trickle left once the tile is clean
+        let active = removed > 1e-3;
+        if scrubber.active != active {
+            scrubber.active = active;
+        }
+    }
+}
+
+/// Tints vents with their mix and scrubbers with what they filter while an overlay is shown.
+/// Sprites are only written when the overlay or a machine changes, so other tints stay
+fn life_support_overlay(
+    mut vent_query: Query<
+        (
+            &Vent,
+            ChangeTrackers<Vent>,
+            &Graphic,
+            &mut TextureAtlasSprite,
+        ),
+        Without<Scrubber>,
+    >,
+    mut scrubber_query: Query<(
+        &Scrubber,
+        ChangeTrackers<Scrubber>,
+        &Graphic,
+        &mut TextureAtlasSprite,
+    )>,
+    grid_query: Query<(
+        &GasVisualizationSettings,
+        ChangeTrackers<GasVisualizationSettings>,
+    )>,
+    graphics: Res<Graphics>,
+    gases: Res<GasRegistry>,
+) {
+    let (settings, settings_changed) = grid_query.single();
+    let overlay = !matches!(settings, GasVisualizationSettings::None);
+    let settings_changed = settings_changed.is_changed();
+    //Turning the overlay off puts back the colour from graphics_desc
+    let gas_color = |names: &mut dyn Iterator<Item = &String>, active: bool, graphic: &Graphic| {
+        if !overlay {
+            return graphics.sprite_color(graphic).unwrap_or(Color::WHITE);
+        }
+        let mut color = Vec3::ZERO;
+        let mut count = 0.0;
+        for gas in names.filter_map(|name| gases.get(name)) {
+            let desc_color = gases.desc(gas).color;
+            color += Vec3::new(desc_color.r(), desc_color.g(), desc_color.b());
+            count += 1.0;
+        }
+        let color = color / f32::max(count, 1.0);
+        let alpha = if active { 1.0 } else { 0.4 };
+        Color::rgba(color.x, color.y, color.z, alpha)
+    };
+
+    for (vent, vent_changed, graphic, mut sprite) in vent_query.iter_mut() {
+        if settings_changed || (overlay && vent_changed.is_changed()) {
+            sprite.color = gas_color(
+                &mut vent.mix.iter().map(|share| &share.gas),
+                vent.active,
+                graphic,
+            );
+        }
+    }
+    for (scrubber, scrubber_changed, graphic, mut sprite) in scrubber_query.iter_mut() {
+        if settings_changed || (overlay && scrubber_changed.is_changed()) {
+            sprite.color = gas_color(&mut scrubber.filter.iter(), scrubber.active, graphic);
+        }
+    }
+}
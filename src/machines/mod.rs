@@ -0,0 +1,257 @@
+use bevy::app::PluginGroupBuilder;
+
+use crate::prelude::*;
+
+mod climate;
+mod electrolysis;
+mod furnace;
+mod life_support;
+mod power;
+mod terminal;
+
+pub use furnace::spawn_furnace;
+pub use life_support::spawn_vent;
+pub use power::{balance_grid, power_grid_labels};
+pub use terminal::spawn_terminal;
+
+/// Component: Heats the gas on its tile while on
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Heater {
+    pub watts: f64,
+    pub on: bool,
+}
+
+/// Component: Pulls heat out of the gas on its tile while on
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Cooler {
+    pub watts: f64,
+    pub on: bool,
+}
+
+/// Component: Switches the heaters and coolers in its room to hold the target temperature
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Thermostat {
+    pub target: f64,
+    //Kelvin either side of the target before a machine turns on
+    pub hysteresis: f64,
+}
+
+/// Share of one gas in a vent's output
+#[derive(Inspectable, Deserialize, Clone, Default)]
+pub struct GasShare {
+    pub gas: String,
+    pub fraction: f64,
+}
+
+/// Component: Fills its tile with mix from the life support network up to target_pressure,
+/// or with whatever the pipe holds when it sits on a pipe connector
+#[derive(Component, Inspectable, Deserialize, Clone)]
+pub struct Vent {
+    pub target_pressure: f64,
+    pub rate: f64, // moles/s
+    pub mix: Vec<GasShare>,
+    pub power: f64, // W
+    #[serde(default)]
+    pub active: bool,
+}
+
+/// Component: Pulls the filtered gases out of its tile into the life support network
+#[derive(Component, Inspectable, Deserialize, Clone)]
+pub struct Scrubber {
+    pub filter: Vec<String>,
+    //Fraction of each filtered gas removed per second
+    pub rate: f64,
+    pub power: f64, // W
+    #[serde(default)]
+    pub active: bool,
+}
+
+/// Resource: Tanks shared by every vent and scrubber on the station
+pub struct LifeSupportNetwork {
+    pub supply: GasMixture,
+    pub storage: GasMixture,
+}
+
+/// Component: Splits the water vapor on its tile into hydrogen and oxygen
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Electrolyzer {
+    pub rate: f64,  // moles of water/s
+    pub power: f64, // W
+    #[serde(default)]
+    pub active: bool,
+}
+
+/// Component: Machine drawing watts from the PowerGrid under it, it slows down during a brownout
+#[derive(Component, Inspectable, Clone, Copy, Default)]
+pub struct PowerConsumer {
+    pub watts: f64,
+    //Fraction of watts the grid delivered, 0 when off the wires
+    pub supply: f64,
+}
+
+/// Component: Puts watts into the power grid
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Generator {
+    pub watts: f64,
+}
+
+/// Component: Turns sunlight on the side facing direction into power
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct SolarPanel {
+    pub area: f64, // m^2
+    pub efficiency: f64,
+    #[serde(default)]
+    pub direction: Orientation,
+}
+
+/// Component: Puts watts into the power grid while a furnace next to it is burning
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct FurnaceGenerator {
+    pub watts: f64,
+}
+
+/// Component: Burns fuel with the oxygen on its tile, heating the tile and giving off CO2,
+/// drives furnace generators next to it while burning
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Furnace {
+    pub fuel: f64,           // kg
+    pub burn_rate: f64,      // kg/s
+    pub energy_density: f64, // J/kg
+    //Moles of O2 used, and of CO2 given off, per kg burnt
+    pub oxygen_per_kg: f64,
+    //Goes out when the oxygen on its tile drops below this many atms
+    pub min_oxygen_pressure: f64,
+    pub heat_capacity: f64, // J/K
+    //Heat passed to the tile per kelvin the furnace is above it
+    pub conductance: f64, // W/K
+    pub temperature: f64, // K
+    pub burning: bool,
+}
+
+/// Component: Soaks up spare power and covers shortfalls
+#[derive(Component, Inspectable, Deserialize, Clone, Copy)]
+pub struct Battery {
+    pub capacity: f64, // J
+    pub charge: f64,   // J
+    //Fastest the battery can charge or discharge
+    pub max_rate: f64, // W
+}
+
+/// Component: Wire layer over the gas grid, machines on connected wires share a PowerGrid
+#[derive(Component)]
+pub struct WireGrid {
+    pub tile_size: f32,
+    pub wires: [[Option<Entity>; GRID_SIZE]; GRID_SIZE],
+}
+
+/// Component: Wire tile
+#[derive(Component, Inspectable, Clone, Copy, Default)]
+pub struct Wire;
+
+/// Power through one set of connected wires on the last tick
+#[derive(Inspectable, Clone, Copy, Default, Debug)]
+pub struct PowerGrid {
+    pub supply: f64, // W
+    pub demand: f64, // W
+    pub stored: f64, // J
+    //Fraction of the demand that was met, below 1 is a brownout
+    pub satisfaction: f64,
+}
+
+/// Component: Every PowerGrid on the wire layer, labels give the grid under each wire tile
+#[derive(Component)]
+pub struct PowerGrids {
+    pub grids: Vec<PowerGrid>,
+    pub labels: [[Option<usize>; GRID_SIZE]; GRID_SIZE],
+}
+
+/// Component: Opens the station console when clicked while powered
+#[derive(Component, Inspectable, Clone, Copy, Default)]
+pub struct Terminal;
+
+/// Resource: Station console, open while terminal is set
+#[derive(Default)]
+pub struct Console {
+    pub terminal: Option<Entity>,
+    pub input: String,
+    pub history: Vec<String>,
+    //Entered line waiting to be run
+    pending: Option<String>,
+}
+
+impl Console {
+    pub fn is_open(&self) -> bool {
+        self.terminal.is_some()
+    }
+}
+
+/// Command typed into the console
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleCommand {
+    Help,
+    Status,
+    Rooms,
+    //Lists the vents in a room, or sets their target pressure
+    Vent { room: usize, target: Option<f64> },
+    Door { id: usize, open: bool },
+    Exit,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<ConsoleCommand, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |word: Option<&&str>, what: &str| {
+            word.and_then(|word| word.parse::<usize>().ok())
+                .ok_or(format!("Expected a {} number", what))
+        };
+        match words.as_slice() {
+            ["help"] => Ok(ConsoleCommand::Help),
+            ["status"] => Ok(ConsoleCommand::Status),
+            ["rooms"] => Ok(ConsoleCommand::Rooms),
+            ["vent", rest @ ..] if rest.len() <= 2 => {
+                let room = number(rest.first(), "room")?;
+                let target = match rest.get(1) {
+                    Some(target) => Some(
+                        target
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|target| *target >= 0.0)
+                            .ok_or("Expected a pressure in atms")?,
+                    ),
+                    None => None,
+                };
+                Ok(ConsoleCommand::Vent { room, target })
+            }
+            ["door", action @ ("open" | "close"), rest @ ..] if rest.len() <= 1 => {
+                Ok(ConsoleCommand::Door {
+                    id: number(rest.first(), "door")?,
+                    open: *action == "open",
+                })
+            }
+            ["exit"] => Ok(ConsoleCommand::Exit),
+            [] => Err("Type a command, try help".to_string()),
+            [word, ..] => Err(format!("Unknown command {}, try help", word)),
+        }
+    }
+}
+
+struct ClimatePlugin;
+struct LifeSupportPlugin;
+struct ElectrolysisPlugin;
+struct FurnacePlugin;
+struct PowerPlugin;
+struct TerminalPlugin;
+
+pub struct MachinesPluginGroup;
+
+impl PluginGroup for MachinesPluginGroup {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(PowerPlugin)
+            .add(ClimatePlugin)
+            .add(LifeSupportPlugin)
+            .add(ElectrolysisPlugin)
+            .add(FurnacePlugin)
+            .add(TerminalPlugin);
+    }
+}
@@ -0,0 +1,379 @@
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::PowerPlugin;
+
+/// Marks the warning sprite shown over an unpowered machine
+#[derive(Component)]
+struct PowerIndicator;
+
+impl Plugin for PowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_power_machines)
+            .add_startup_system(spawn_wires)
+            .add_system(balance_power)
+            .add_system(add_power_indicators)
+            .add_system(update_power_indicators)
+            .add_system_to_stage(CoreStage::PostUpdate, wire_update)
+            .register_inspectable::<PowerConsumer>()
+            .register_inspectable::<Generator>()
+            .register_inspectable::<SolarPanel>()
+            .register_inspectable::<FurnaceGenerator>()
+            .register_inspectable::<Battery>()
+            .register_inspectable::<Wire>();
+    }
+}
+
+/// Balances one PowerGrid for dt seconds, surplus charges the batteries and shortfalls drain them,
+/// returns the fraction of the demand that could be met
+pub fn balance_grid(supply: f64, demand: f64, batteries: &mut [Battery], dt: f64) -> f64 {
+    if supply >= demand {
+        let mut surplus = supply - demand;
+        for battery in batteries.iter_mut() {
+            let watts = surplus
+                .min(battery.max_rate)
+                .min((battery.capacity - battery.charge) / dt);
+            if watts > 0.0 {
+                battery.charge += watts * dt;
+                surplus -= watts;
+            }
+        }
+        return 1.0;
+    }
+
+    let mut shortfall = demand - supply;
+    for battery in batteries.iter_mut() {
+        let watts = shortfall.min(battery.max_rate).min(battery.charge / dt);
+        if watts > 0.0 {
+            battery.charge -= watts * dt;
+            shortfall -= watts;
+        }
+    }
+    (demand - shortfall) / demand
+}
+
+/// Labels each set of connected wire tiles with its own index, the labels and how many sets
+/// there are. Tiles off the wires have no label so nothing there gets power
+pub fn power_grid_labels<const N: usize>(
+    wires: &[[bool; N]; N],
+) -> (Box<[[Option<usize>; N]; N]>, usize) {
+    let mut labels: Box<[[Option<usize>; N]; N]> = new_boxed_grid(None);
+    let mut count = 0;
+    for i in 0..N {
+        for j in 0..N {
+            if !wires[i][j] || labels[i][j].is_some() {
+                continue;
+            }
+            labels[i][j] = Some(count);
+            let mut to_visit = vec![(i, j)];
+            while let Some((x, y)) = to_visit.pop() {
+                for (nx, ny) in neighbours(x, y, N) {
+                    if wires[nx][ny] && labels[nx][ny].is_none() {
+                        labels[nx][ny] = Some(count);
+                        to_visit.push((nx, ny));
+                    }
+                }
+            }
+            count += 1;
+        }
+    }
+    (labels, count)
+}
+
+pub fn spawn_generator(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Generator))
+        .insert(comp_from_config!(Generator))
+        .insert(Name::new("Generator"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+pub fn spawn_battery(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Battery))
+        .insert(comp_from_config!(Battery))
+        .insert(Name::new("Battery"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+pub fn spawn_solar_panel(
+    commands: &mut Commands,
+    position: Vec3,
+    direction: Orientation,
+) -> Entity {
+    let mut panel = comp_from_config!(SolarPanel);
+    panel.direction = direction;
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::SolarPanel))
+        .insert(panel)
+        .insert(Name::new("Solar Panel"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+pub fn spawn_furnace_generator(commands: &mut Commands, position: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::FurnaceGenerator))
+        .insert(comp_from_config!(FurnaceGenerator))
+        .insert(Name::new("Furnace Generator"))
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+//Powers the station from the reinforced room, backed up by the furnace and a panel outside
+fn spawn_power_machines(mut commands: Commands) {
+    spawn_generator(&mut commands, Vec3::new(128.0, -32.0, 100.0));
+    spawn_battery(&mut commands, Vec3::new(192.0, -32.0, 100.0));
+    spawn_furnace_generator(&mut commands, Vec3::new(-192.0, 32.0, 100.0));
+    spawn_solar_panel(
+        &mut commands,
+        Vec3::new(352.0, -32.0, 100.0),
+        Orientation::Right,
+    );
+}
+
+fn create_wire(commands: &mut Commands, grid: &mut WireGrid, x: usize, y: usize) {
+    if grid.wires[x][y].is_none() {
+        let ent = commands
+            .spawn()
+            .insert(Graphic::WorldObject(WorldObject::Wire(
+                WallConnection::None,
+            )))
+            .insert(TileOrientation::default())
+            .insert(Transform::from_xyz(
+                x as f32 * grid.tile_size,
+                y as f32 * grid.tile_size,
+                0.0,
+            ))
+            .insert(Wire)
+            .insert(Name::new("Wire"))
+            .id();
+        grid.wires[x][y] = Some(ent);
+    }
+}
+
+fn spawn_wires(mut commands: Commands) {
+    let mut grid = WireGrid {
+        tile_size: 32.0,
+        wires: [[None; GRID_SIZE]; GRID_SIZE],
+    };
+    //Main line from the furnace through every room out to the solar panel
+    for x in 19..=36 {
+        create_wire(&mut commands, &mut grid, x, 24);
+    }
+    //Branches to the machines off the main line
+    for (x, y) in [
+        (19, 25),
+        (19, 26),
+        (21, 25),
+        (23, 25),
+        (24, 25),
+        (24, 26),
+        (25, 23),
+        (26, 25),
+        (33, 25),
+    ] {
+        create_wire(&mut commands, &mut grid, x, y);
+    }
+
+    let children: Vec<Entity> = grid.wires.iter().flatten().flatten().copied().collect();
+    let offset = GRID_SIZE as f32 * grid.tile_size / 2.0;
+    commands
+        .spawn()
+        .push_children(&children)
+        .insert(GlobalTransform::default())
+        .insert(Transform::from_xyz(-offset, -offset, 40.0))
+        .insert(grid)
+        .insert(PowerGrids {
+            grids: Vec::new(),
+            labels: [[None; GRID_SIZE]; GRID_SIZE],
+        })
+        .insert(Name::new("Wire Grid"));
+}
+
+//Each set of connected wires is its own PowerGrid
+fn find_power_grids(grid: &WireGrid, power_grids: &mut PowerGrids) {
+    let mut wires = [[false; GRID_SIZE]; GRID_SIZE];
+    for (wired, row) in wires.iter_mut().zip(grid.wires.iter()) {
+        for (wired, wire) in wired.iter_mut().zip(row.iter()) {
+            *wired = wire.is_some();
+        }
+    }
+    let (labels, count) = power_grid_labels(&wires);
+    power_grids.labels = *labels;
+    power_grids.grids = vec![PowerGrid::default(); count];
+}
+
+fn wire_update(
+    mut wire_query: Query<(&mut Graphic, &mut TileOrientation), With<Wire>>,
+    mut grid_query: Query<(&WireGrid, &mut PowerGrids), Changed<WireGrid>>,
+    rules: Res<AutotileRules>,
+) {
+    for (grid, mut power_grids) in grid_query.iter_mut() {
+        find_power_grids(grid, &mut power_grids);
+        for (i, row) in grid.wires.iter().enumerate() {
+            for (j, ent) in row.iter().enumerate() {
+                if let Some(ent) = ent {
+                    if let Ok((mut graphic, mut orientation)) = wire_query.get_mut(*ent) {
+                        let rule = line_autotile(&rules, &grid.wires, i, j);
+                        *graphic = Graphic::WorldObject(WorldObject::Wire(rule.connection));
+                        *orientation = rule.orientation;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn normal(direction: Orientation) -> Vec2 {
+    match direction {
+        Orientation::Up => Vec2::Y,
+        Orientation::Down => -Vec2::Y,
+        Orientation::Left => -Vec2::X,
+        Orientation::Right => Vec2::X,
+    }
+}
+
+//Machines join the PowerGrid of the wire under them, anything off the wires gets no power
+fn balance_power(
+    mut grid_query: Query<(&WireGrid, &mut PowerGrids, &GlobalTransform)>,
+    generator_query: Query<(&GlobalTransform, &Generator)>,
+    solar_query: Query<(&GlobalTransform, &SolarPanel)>,
+    furnace_generator_query: Query<(&GlobalTransform, &FurnaceGenerator)>,
+    furnace_query: Query<(&GlobalTransform, &Furnace)>,
+    mut battery_query: Query<(&GlobalTransform, &mut Battery)>,
+    mut consumer_query: Query<(&GlobalTransform, &mut PowerConsumer)>,
+    radiation_query: Query<&Radiation>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds() as f64;
+    if dt <= 0.0 {
+        return;
+    }
+    let (grid, mut power_grids, grid_transform) = grid_query.single_mut();
+    let labels = power_grids.labels;
+    let grid_of = |transform: &GlobalTransform| {
+        world_to_tile(
+            transform.translation.truncate(),
+            grid_transform.translation.truncate(),
+            grid.tile_size,
+        )
+        .and_then(|(i, j)| labels[i][j])
+    };
+
+    let mut supply = vec![0.0; power_grids.grids.len()];
+    let mut demand = vec![0.0; power_grids.grids.len()];
+
+    for (transform, generator) in generator_query.iter() {
+        if let Some(label) = grid_of(transform) {
+            supply[label] += generator.watts;
+        }
+    }
+
+    let radiation = radiation_query.single();
+    let star = radiation.star_direction(time.seconds_since_startup());
+    for (transform, panel) in solar_query.iter() {
+        if let Some(label) = grid_of(transform) {
+            let sunlight = normal(panel.direction).dot(star).max(0.0) as f64;
+            supply[label] += radiation.star_intensity * sunlight * panel.area * panel.efficiency;
+        }
+    }
+
+    //A furnace within a tile of the generator drives it
+    for (transform, generator) in furnace_generator_query.iter() {
+        if let Some(label) = grid_of(transform) {
+            let driven = furnace_query.iter().any(|(furnace_transform, furnace)| {
+                furnace.burning
+                    && furnace_transform
+                        .translation
+                        .truncate()
+                        .distance(transform.translation.truncate())
+                        <= grid.tile_size * 1.5
+            });
+            if driven {
+                supply[label] += generator.watts;
+            }
+        }
+    }
+
+    for (transform, consumer) in consumer_query.iter() {
+        if let Some(label) = grid_of(transform) {
+            demand[label] += consumer.watts;
+        }
+    }
+
+    let mut satisfaction = vec![0.0; power_grids.grids.len()];
+    for (label, power_grid) in power_grids.grids.iter_mut().enumerate() {
+        let mut batteries: Vec<Mut<Battery>> = battery_query
+            .iter_mut()
+            .filter(|(transform, _)| grid_of(transform) == Some(label))
+            .map(|(_, battery)| battery)
+            .collect();
+        let mut charge: Vec<Battery> = batteries.iter().map(|battery| **battery).collect();
+        satisfaction[label] = balance_grid(supply[label], demand[label], &mut charge, dt);
+        for (battery, charged) in batteries.iter_mut().zip(charge.iter()) {
+            battery.charge = charged.charge;
+        }
+
+        *power_grid = PowerGrid {
+            supply: supply[label],
+            demand: demand[label],
+            stored: charge.iter().map(|battery| battery.charge).sum(),
+            satisfaction: satisfaction[label],
+        };
+    }
+
+    for (transform, mut consumer) in consumer_query.iter_mut() {
+        let supplied = grid_of(transform).map_or(0.0, |label| satisfaction[label]);
+        if consumer.supply != supplied {
+            consumer.supply = supplied;
+        }
+    }
+}
+
+fn add_power_indicators(
+    mut commands: Commands,
+    consumer_query: Query<Entity, Added<PowerConsumer>>,
+) {
+    for ent in consumer_query.iter() {
+        let indicator = commands
+            .spawn()
+            .insert(Graphic::WorldObject(WorldObject::Unpowered))
+            .insert(PowerIndicator)
+            .insert(Name::new("Unpowered"))
+            .insert(Transform::from_xyz(0.0, 12.0, 0.1))
+            .id();
+        commands.entity(ent).add_child(indicator);
+    }
+}
+
+//Fades in as the supply drops so brownouts show up too
+fn update_power_indicators(
+    consumer_query: Query<(&PowerConsumer, &Children)>,
+    mut indicator_query: Query<(&mut Visibility, &mut TextureAtlasSprite), With<PowerIndicator>>,
+) {
+    for (consumer, children) in consumer_query.iter() {
+        for child in children.iter() {
+            if let Ok((mut visibility, mut sprite)) = indicator_query.get_mut(*child) {
+                let unpowered = consumer.watts > 0.0 && consumer.supply < 1.0;
+                if visibility.is_visible != unpowered {
+                    visibility.is_visible = unpowered;
+                }
+                sprite
+                    .color
+                    .set_a(0.3 + 0.7 * (1.0 - consumer.supply as f32));
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use bevy::{render::camera::Camera2d, window::ReceivedCharacter};
+use bevy_inspector_egui::RegisterInspectable;
+
+use crate::prelude::*;
+
+use super::TerminalPlugin;
+
+//Size of the console in ascii tiles, text gets a one tile border
+const CONSOLE_WIDTH: usize = 44;
+const CONSOLE_HEIGHT: usize = 14;
+const LINE_LENGTH: usize = CONSOLE_WIDTH - 4;
+const CONSOLE_SCALE: f32 = 0.5;
+
+impl Plugin for TerminalPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Console::default())
+            .add_startup_system(spawn_terminals)
+            .add_system(open_console)
+            .add_system(console_input)
+            .add_system(run_console_command.after(console_input))
+            .add_system(draw_console.after(run_console_command))
+            .register_inspectable::<Terminal>();
+    }
+}
+
+pub fn spawn_terminal(commands: &mut Commands, position: Vec3, facing: Orientation) -> Entity {
+    commands
+        .spawn()
+        .insert(Graphic::WorldObject(WorldObject::Terminal(facing)))
+        .insert(Name::new("Terminal"))
+        .insert(Terminal)
+        .insert(PowerConsumer {
+            watts: 50.0,
+            ..Default::default()
+        })
+        .insert(Clickable)
+        .insert(Transform::from_translation(position))
+        .id()
+}
+
+fn spawn_terminals(mut commands: Commands) {
+    spawn_terminal(
+        &mut commands,
+        Vec3::new(-32.0, 32.0, 100.0),
+        Orientation::Left,
+    );
+    spawn_terminal(
+        &mut commands,
+        Vec3::new(32.0, -32.0, 100.0),
+        Orientation::Right,
+    );
+    spawn_terminal(
+        &mut commands,
+        Vec3::new(-32.0, -32.0, 100.0),
+        Orientation::Down,
+    );
+}
+
+fn open_console(
+    terminal_query: Query<Option<&PowerConsumer>, With<Terminal>>,
+    mut clicks: EventReader<EntityClicked>,
+    mut console: ResMut<Console>,
+    build: Res<BuildMode>,
+) {
+    for click in clicks.iter() {
+        //build_click has the clicks while in build mode
+        if click.button != ClickButton::Left || build.active {
+            continue;
+        }
+        let ent = click.entity;
+        let consumer = match terminal_query.get(ent) {
+            Ok(consumer) => consumer,
+            Err(_) => continue,
+        };
+        if consumer.is_some_and(|consumer| consumer.supply <= 0.0) {
+            info!("Terminal has no power");
+            continue;
+        }
+        if console.terminal != Some(ent) {
+            console.terminal = Some(ent);
+            console.input.clear();
+            console.history = vec!["STATION CONSOLE - type help".to_string()];
+        }
+    }
+}
+
+fn console_input(
+    mut console: ResMut<Console>,
+    mut characters: EventReader<ReceivedCharacter>,
+    keyboard: Res<Input<KeyCode>>,
+) {
+    if !console.is_open() {
+        //Drop what was typed while it was closed
+        for _event in characters.iter() {}
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Escape) {
+        console.terminal = None;
+        return;
+    }
+    for event in characters.iter() {
+        //Ascii sheet only has the first 256 characters
+        if event.char.is_ascii()
+            && !event.char.is_ascii_control()
+            && console.input.len() < LINE_LENGTH - 2
+        {
+            console.input.push(event.char);
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keyboard.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        console.history.push(format!("> {}", line));
+        console.pending = Some(line);
+    }
+}
+
+fn run_console_command(
+    mut console: ResMut<Console>,
+    grid_query: Query<(&GasGrid, &GlobalTransform)>,
+    tile_query: Query<&GasMixture>,
+    mut vent_query: Query<(&GlobalTransform, &mut Vent)>,
+    power_query: Query<&PowerGrids>,
+    mut door_query: Query<&mut Door>,
+    network: Res<LifeSupportNetwork>,
+) {
+    //Only take the line once there is one, taking it marks the console changed
+    if console.pending.is_none() {
+        return;
+    }
+    let line = console.pending.take().unwrap();
+    let (grid, grid_transform) = grid_query.single();
+
+    let output = match ConsoleCommand::parse(&line) {
+        Err(error) => vec![error],
+        Ok(ConsoleCommand::Help) => vec![
+            "status - power and life support".to_string(),
+            "rooms - atmosphere of each room".to_string(),
+            "vent <room> [atms] - list or set vents".to_string(),
+            "door open|close <id>".to_string(),
+            "exit - close the console".to_string(),
+        ],
+        Ok(ConsoleCommand::Status) => {
+            let mut output = Vec::new();
+            for (i, power_grid) in power_query
+                .iter()
+                .flat_map(|grids| grids.grids.iter())
+                .enumerate()
+            {
+                output.push(format!(
+                    "Power {}: {:.0}W/{:.0}W {:.0}kJ {:.0}%",
+                    i,
+                    power_grid.supply,
+                    power_grid.demand,
+                    power_grid.stored / 1000.0,
+                    power_grid.satisfaction * 100.0
+                ));
+            }
+            output.push(format!(
+                "Life support: {:.0} mol supply",
+                network.supply.total_moles()
+            ));
+            output.push(format!(
+                "              {:.0} mol scrubbed",
+                network.storage.total_moles()
+            ));
+            output
+        }
+        Ok(ConsoleCommand::Rooms) => {
+            let rooms = grid.rooms();
+            if rooms.is_empty() {
+                vec!["No sealed rooms".to_string()]
+            } else {
+                rooms
+                    .iter()
+                    .enumerate()
+                    .map(|(id, room)| {
+                        let tiles: Vec<&GasMixture> = room
+                            .iter()
+                            .map(|(i, j)| tile_query.get(grid.grid[*i][*j]).unwrap())
+                            .collect();
+                        let count = tiles.len() as f64;
+                        let pressure: f64 = tiles
+                            .iter()
+                            .map(|tile| tile.get_total_pressure() as f64)
+                            .sum();
+                        let temperature: f64 = tiles.iter().map(|tile| tile.temperature).sum();
+                        format!(
+                            "Room {}: {} tiles {:.2}atm {:.0}K",
+                            id,
+                            room.len(),
+                            pressure / count,
+                            temperature / count
+                        )
+                    })
+                    .collect()
+            }
+        }
+        Ok(ConsoleCommand::Vent { room, target }) => match grid.rooms().get(room) {
+            None => vec![format!("No room {}", room)],
+            Some(tiles) => {
+                let mut output = Vec::new();
+                for (transform, mut vent) in vent_query.iter_mut() {
+                    let tile = world_to_tile(
+                        transform.translation.truncate(),
+                        grid_transform.translation.truncate(),
+                        grid.tile_size,
+                    );
+                    if !tile.is_some_and(|tile| tiles.contains(&tile)) {
+                        continue;
+                    }
+                    if let Some(target) = target {
+                        vent.target_pressure = target;
+                    }
+                    output.push(format!(
+                        "Vent {}: target {:.2}atm {}",
+                        output.len(),
+                        vent.target_pressure,
+                        if vent.active { "venting" } else { "idle" }
+                    ));
+                }
+                if output.is_empty() {
+                    output.push(format!("No vents in room {}", room));
+                }
+                output
+            }
+        },
+        Ok(ConsoleCommand::Door { id, open }) => {
+            match door_query.iter_mut().find(|door| door.id == id) {
+                None => vec![format!("No door {}", id)],
+                Some(mut door) => {
+                    door.open = open;
+                    vec![format!(
+                        "Door {} {}",
+                        id,
+                        if open { "opened" } else { "closed" }
+                    )]
+                }
+            }
+        }
+        Ok(ConsoleCommand::Exit) => {
+            console.terminal = None;
+            Vec::new()
+        }
+    };
+
+    for line in output {
+        console.history.push(line);
+    }
+}
+
+//Rebuilt whenever the console changes, it is only a few hundred sprites
+fn draw_console(
+    mut commands: Commands,
+    console: Res<Console>,
+    ascii: Res<AsciiSheet>,
+    indices: Res<NineSliceIndices>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+    mut root: Local<Option<Entity>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    if let Some(root) = root.take() {
+        commands.entity(root).despawn_recursive();
+    }
+    if !console.is_open() {
+        return;
+    }
+
+    let camera = camera_query.single();
+    let nine_slice = spawn_nine_slice(
+        &mut commands,
+        &ascii,
+        &indices,
+        CONSOLE_WIDTH as f32,
+        CONSOLE_HEIGHT as f32,
+    );
+
+    //Wrap long lines and keep the newest that fit above the prompt
+    let mut lines: Vec<String> = console
+        .history
+        .iter()
+        .flat_map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            chars
+                .chunks(LINE_LENGTH)
+                .map(|chunk| chunk.iter().collect::<String>())
+                .collect::<Vec<String>>()
+        })
+        .collect();
+    let shown = CONSOLE_HEIGHT - 3;
+    if lines.len() > shown {
+        lines.drain(..lines.len() - shown);
+    }
+    lines.push(format!("> {}_", console.input));
+
+    let left = (-(CONSOLE_WIDTH as f32) / 2.0 + 2.0) * ascii.tile_size;
+    let mut children = vec![nine_slice];
+    for (row, line) in lines.iter().enumerate() {
+        let up = (CONSOLE_HEIGHT as f32 / 2.0 - 1.5 - row as f32) * ascii.tile_size;
+        children.push(spawn_ascii_text(
+            &mut commands,
+            &ascii,
+            line,
+            Vec3::new(left, up, 0.1),
+        ));
+    }
+
+    let console_root = commands
+        .spawn()
+        .insert(Transform {
+            translation: Vec3::new(camera.translation.x, camera.translation.y, 950.0),
+            scale: Vec3::splat(CONSOLE_SCALE),
+            ..Default::default()
+        })
+        .insert(GlobalTransform::default())
+        .insert(Name::new("Console"))
+        .push_children(&children)
+        .id();
+    *root = Some(console_root);
+}
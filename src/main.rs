@@ -1,6 +1,4 @@
 #![allow(clippy::redundant_field_names)]
-#![allow(clippy::type_complexity)]
-#![allow(clippy::too_many_arguments)]
 use std::fs;
 
 use bevy::{prelude::*, render::camera::ScalingMode, window::PresentMode};
@@ -9,18 +7,13 @@ use bevy::{prelude::*, render::camera::ScalingMode, window::PresentMode};
 pub const CLEAR: Color = Color::rgb(0.0, 0.0, 0.0);
 pub const RESOLUTION: f32 = 16.0 / 9.0;
 
-mod debug;
-mod graphics;
-mod grid;
-mod mouse;
-mod player;
-mod prelude;
-
-use debug::DebugPlugin;
-use graphics::GraphicsPluginGroup;
-use grid::GridPluginGroup;
-use mouse::MousePlugin;
-use player::{Player, PlayerPlugin};
+use bevy_space_game::build::BuildPluginGroup;
+use bevy_space_game::debug::DebugPlugin;
+use bevy_space_game::graphics::GraphicsPluginGroup;
+use bevy_space_game::grid::GridPluginGroup;
+use bevy_space_game::machines::{Console, MachinesPluginGroup};
+use bevy_space_game::mouse::MousePlugin;
+use bevy_space_game::player::{Player, PlayerPlugin};
 use ron::from_str;
 use serde::{Deserialize, Serialize};
 
@@ -46,6 +39,8 @@ fn main() {
         .add_plugin(DebugPlugin)
         .add_plugins(GridPluginGroup)
         .add_plugins(GraphicsPluginGroup)
+        .add_plugins(MachinesPluginGroup)
+        .add_plugins(BuildPluginGroup)
         .add_state(AppState::Game)
         .add_plugin(MousePlugin)
         .add_startup_system(spawn_camera)
@@ -84,8 +79,12 @@ struct SaveFile {
     player_translation: Vec3,
 }
 
-fn save_game(player_query: Query<&Transform, With<Player>>, keyboard: Res<Input<KeyCode>>) {
-    if keyboard.just_pressed(KeyCode::O) {
+fn save_game(
+    player_query: Query<&Transform, With<Player>>,
+    keyboard: Res<Input<KeyCode>>,
+    console: Res<Console>,
+) {
+    if !console.is_open() && keyboard.just_pressed(KeyCode::O) {
         let transform = player_query.single();
 
         let save_file = SaveFile {
@@ -100,8 +99,12 @@ fn save_game(player_query: Query<&Transform, With<Player>>, keyboard: Res<Input<
     }
 }
 
-fn load_game(mut player_query: Query<&mut Transform, With<Player>>, keyboard: Res<Input<KeyCode>>) {
-    if keyboard.just_pressed(KeyCode::L) {
+fn load_game(
+    mut player_query: Query<&mut Transform, With<Player>>,
+    keyboard: Res<Input<KeyCode>>,
+    console: Res<Console>,
+) {
+    if !console.is_open() && keyboard.just_pressed(KeyCode::L) {
         let save_file: SaveFile =
             from_str(&fs::read_to_string("saves/save1.ron").unwrap()).expect("Failed to load ron");
 
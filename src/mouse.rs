@@ -9,7 +9,7 @@ pub struct MousePosition(pub Vec2);
 
 impl Plugin for MousePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(CoreStage::PreUpdate, mouse_position)
+        app.add_system_to_stage(CoreStage::PreUpdate, mouse_position.label("mouse_position"))
             .insert_resource(MousePosition(Vec2::default()));
     }
 }
@@ -33,7 +33,7 @@ fn mouse_position(
         // check if the cursor is inside the window and get its position
         if let Some(screen_pos) = wnd.cursor_position() {
             // get the size of the window
-            let window_size = Vec2::new(wnd.width() as f32, wnd.height() as f32);
+            let window_size = Vec2::new(wnd.width(), wnd.height());
 
             // convert screen position [0..resolution] to ndc [-1..1] (gpu coordinates)
             let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
@@ -11,6 +11,8 @@ use crate::prelude::*;
 pub struct Player {
     move_speed: f32,
     breath_rate: f32,
+    //Tile toxicity the player can breathe without harm
+    toxic_limit: f64,
     hitbox: Vec2,
 }
 
@@ -19,7 +21,6 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(spawn_player)
-            .add_startup_system(spawn_terminal)
             .add_system(player_breath)
             .add_system(camera_follow)
             .add_system(player_collision.after(player_movement))
@@ -30,11 +31,17 @@ impl Plugin for PlayerPlugin {
 fn player_collision(
     mut player_query: Query<(&mut Transform, &Player)>,
     grid_query: Query<&WallGrid>,
-    wall_query: Query<&GlobalTransform, (With<Wall>, Without<Player>)>,
+    wall_query: Query<
+        (&GlobalTransform, Option<&Door>),
+        (Or<(With<Wall>, With<Door>)>, Without<Player>),
+    >,
 ) {
     let grid = grid_query.single();
     let (mut player_transform, player) = player_query.single_mut();
-    for wall_transform in wall_query.iter() {
+    for (wall_transform, door) in wall_query.iter() {
+        if door.is_some_and(|door| door.open) {
+            continue;
+        }
         match collide(
             player_transform.translation,
             player.hitbox,
@@ -67,8 +74,15 @@ fn player_breath(
     gas_query: Query<(&GasGrid, &GlobalTransform), Without<Player>>,
     mut tile_query: Query<&mut GasMixture>,
     mut text_query: Query<&mut Text, With<GasText>>,
+    mut was_toxic: Local<bool>,
+    gases: Res<GasRegistry>,
     time: Res<Time>,
 ) {
+    let oxygen = gases.get("Oxygen").expect("Oxygen missing from gases.ron");
+    let carbon_dioxide = gases
+        .get("CarbonDioxide")
+        .expect("CarbonDioxide missing from gases.ron");
+
     let (transform, player) = player_query.single();
     let (gas_grid, gas_transform) = gas_query.single();
 
@@ -83,83 +97,42 @@ fn player_breath(
 
     let mut tile = tile_query.get_mut(tile).unwrap();
     let to_breath = (player.breath_rate * time.delta_seconds()) as f64;
+    let toxicity = tile.toxicity(&gases);
     text_query.single_mut().sections[0].value = format!(
-        "At Player: {:.0}° \nO2: {:.0} moles, {:.2} atms\nCO2: {:.0} moles, {:.2} atms",
+        "At Player: {:.0}° \nO2: {:.0} moles, {:.2} atms\nCO2: {:.0} moles, {:.2} atms\nToxicity: {:.2}",
         tile.temperature,
-        tile.amount[1],
-        tile.get_pressure(Gas::Oxygen),
-        tile.amount[Gas::CarbonDioxide as usize],
-        tile.get_pressure(Gas::CarbonDioxide),
+        tile.amount[oxygen.0],
+        tile.get_pressure(oxygen),
+        tile.amount[carbon_dioxide.0],
+        tile.get_pressure(carbon_dioxide),
+        toxicity,
     );
-    if to_breath < tile.amount[Gas::Oxygen as usize] {
-        tile.amount[Gas::Oxygen as usize] -= to_breath;
-        tile.amount[Gas::CarbonDioxide as usize] += to_breath;
+    //Warn once on going over the limit rather than every frame spent there
+    let is_toxic = toxicity > player.toxic_limit;
+    if is_toxic && !*was_toxic {
+        warn!("Player breathing toxic gas, toxicity {:.2}", toxicity);
+    }
+    *was_toxic = is_toxic;
+    if to_breath < tile.amount[oxygen.0] {
+        tile.amount[oxygen.0] -= to_breath;
+        tile.amount[carbon_dioxide.0] += to_breath;
     } else {
-        tile.amount[Gas::CarbonDioxide as usize] += tile.amount[Gas::Oxygen as usize];
-        tile.amount[Gas::Oxygen as usize] = 0.0;
+        tile.amount[carbon_dioxide.0] += tile.amount[oxygen.0];
+        tile.amount[oxygen.0] = 0.0;
         println!("Player suffocating!");
     }
 }
 
-fn spawn_terminal(mut commands: Commands, assets: Res<AssetServer>) {
-    let ent = commands
-        .spawn()
-        .insert(Graphic::WorldObject(WorldObject::Terminal(
-            Orientation::Left,
-        )))
-        .id();
-    commands
-        .entity(ent)
-        .insert(Name::new("Terminal"))
-        .insert(Clickable::default())
-        .insert(Transform::from_xyz(-32.0, 32.0, 100.0));
-
-    let ent = commands
-        .spawn()
-        .insert(Graphic::WorldObject(WorldObject::Furance(true)))
-        .id();
-    commands
-        .entity(ent)
-        .insert(Name::new("Furance"))
-        .insert(Clickable::default())
-        .insert(Transform::from_xyz(-220.0, 32.0, 100.0));
-    spawn_particle_spawner(
-        &mut commands,
-        "config/fire_particle_spawner.ron",
-        Vec3::new(-230.0, 41.5, 900.0),
-        &assets,
-    );
-
-    let ent = commands
-        .spawn()
-        .insert(Graphic::WorldObject(WorldObject::Terminal(
-            Orientation::Right,
-        )))
-        .id();
-    commands
-        .entity(ent)
-        .insert(Name::new("Terminal"))
-        .insert(Clickable::default())
-        .insert(Transform::from_xyz(32.0, -32.0, 100.0));
-
-    let ent = commands
-        .spawn()
-        .insert(Graphic::WorldObject(WorldObject::Terminal(
-            Orientation::Down,
-        )))
-        .id();
-    commands
-        .entity(ent)
-        .insert(Name::new("Terminal"))
-        .insert(Clickable::default())
-        .insert(Transform::from_xyz(-32.0, -32.0, 100.0));
-}
-
 fn player_movement(
     mut player_query: Query<(&Player, &mut Transform, &mut Graphic)>,
     keyboard: Res<Input<KeyCode>>,
+    console: Res<Console>,
     time: Res<Time>,
 ) {
+    //Keys go to the console while it is open
+    if console.is_open() {
+        return;
+    }
     let (player, mut transform, mut graphic) = player_query.single_mut();
 
     let mut y_delta = 0.0;
@@ -202,6 +175,6 @@ fn spawn_player(mut commands: Commands) {
         .insert(Graphic::Player(Orientation::Down))
         .insert(comp_from_config!(Player))
         .insert(Transform::from_xyz(0.0, 0.0, 500.0))
-        .insert(Clickable::default())
+        .insert(Clickable)
         .insert(Name::new("Player"));
 }
@@ -3,8 +3,10 @@ pub use bevy::utils::HashMap;
 pub use bevy_inspector_egui::Inspectable;
 pub use serde::{Deserialize, Serialize};
 
+pub use crate::build::*;
 pub use crate::graphics::*;
 pub use crate::grid::*;
+pub use crate::machines::*;
 
 pub use crate::comp_from_config;
 
@@ -20,7 +22,7 @@ macro_rules! comp_from_config {
         .expect(&("Failed to load ".to_owned() + &stringify!($comp_type).to_lowercase() + ".ron"))
     };
     ($comp_type:ty,$file_name:expr) => {
-        ron::from_str::<$comp_type>(&std::fs::read_to_string(($file_name)).unwrap())
+        ron::from_str::<$comp_type>(&std::fs::read_to_string($file_name).unwrap())
             .expect(&("Failed to load ".to_owned() + $file_name))
     };
 }
@@ -0,0 +1,211 @@
+use bevy_space_game::prelude::*;
+
+fn empty_walls() -> WallGrid {
+    WallGrid {
+        tile_size: 32.0,
+        walls: [[None; GRID_SIZE]; GRID_SIZE],
+    }
+}
+
+#[test]
+fn every_buildable_has_a_cost() {
+    let costs = comp_from_config!(BuildCosts, "config/buildcosts.ron");
+    for buildable in Buildable::PALETTE {
+        assert!(costs.cost(buildable) > 0.0, "{:?} is free", buildable);
+    }
+    assert!((0.0..=1.0).contains(&costs.refund));
+}
+
+#[test]
+fn stockpile_only_spends_what_it_has() {
+    let mut stockpile = Stockpile { metal: 10.0 };
+    assert!(stockpile.spend(6.0));
+    assert_eq!(stockpile.metal, 4.0);
+    assert!(!stockpile.spend(6.0));
+    assert_eq!(stockpile.metal, 4.0);
+}
+
+#[test]
+fn placement_needs_a_free_tile_and_metal() {
+    let mut walls = empty_walls();
+    let stockpile = Stockpile { metal: 10.0 };
+    let occupied = [(3, 4)];
+
+    assert_eq!(
+        placement_error((5, 5), &walls, &occupied, 5.0, &stockpile),
+        None
+    );
+    assert!(placement_error((3, 4), &walls, &occupied, 5.0, &stockpile).is_some());
+    assert!(placement_error((5, 5), &walls, &occupied, 50.0, &stockpile).is_some());
+
+    walls.walls[5][5] = Some(Entity::from_raw(0));
+    assert!(placement_error((5, 5), &walls, &occupied, 5.0, &stockpile).is_some());
+}
+
+//Every step moves to a side neighbour, so gas can not slip through diagonally
+fn is_sealed(tiles: &[(usize, usize)]) -> bool {
+    tiles.windows(2).all(|pair| {
+        let (a, b) = (pair[0], pair[1]);
+        a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1
+    })
+}
+
+#[test]
+fn lines_run_end_to_end() {
+    assert_eq!(line_tiles((2, 3), (2, 3)), vec![(2, 3)]);
+    assert_eq!(
+        line_tiles((5, 1), (2, 1)),
+        vec![(5, 1), (4, 1), (3, 1), (2, 1)]
+    );
+
+    for end in [(10, 4), (4, 10), (0, 0), (10, 10), (7, 0)] {
+        let tiles = line_tiles((4, 4), end);
+        assert_eq!(tiles.first(), Some(&(4, 4)));
+        assert_eq!(tiles.last(), Some(&end));
+        assert!(is_sealed(&tiles), "gap in line to {:?}", end);
+    }
+}
+
+#[test]
+fn rectangles_match_create_room() {
+    let hollow = rectangle_tiles((4, 6), (1, 2), false);
+    //Same border a 4x5 create_room puts walls on
+    assert_eq!(hollow.len(), 2 * 4 + 2 * 5 - 4);
+    assert!(hollow.contains(&(1, 2)) && hollow.contains(&(4, 6)));
+    assert!(!hollow.contains(&(2, 3)));
+
+    let filled = WallShape::FilledRectangle.tiles((1, 2), (4, 6));
+    assert_eq!(filled.len(), 4 * 5);
+    assert!(filled.contains(&(2, 3)));
+
+    assert_eq!(WallShape::Rectangle.tiles((3, 3), (3, 3)), vec![(3, 3)]);
+}
+
+fn wall_edit(x: usize) -> Edit {
+    Edit {
+        kind: EditKind::AddWall {
+            tile: (x, 0),
+            material: WallMaterial::default(),
+        },
+        metal: -5.0,
+    }
+}
+
+#[test]
+fn history_records_steps_and_forgets_redo() {
+    let mut history = EditHistory::default();
+    history.record(Vec::new());
+    assert!(history.undo.is_empty());
+
+    history.record(vec![wall_edit(0), wall_edit(1)]);
+    history.redo.push(vec![wall_edit(2)]);
+    history.record(vec![wall_edit(3)]);
+    assert_eq!(history.undo.len(), 2);
+    assert!(history.redo.is_empty());
+
+    for x in 0..150 {
+        history.record(vec![wall_edit(x)]);
+    }
+    assert_eq!(history.undo.len(), 100);
+    match history.undo[0][0].kind {
+        EditKind::AddWall { tile, .. } => assert_eq!(tile, (50, 0)),
+        _ => panic!("oldest step is not a wall"),
+    }
+}
+
+const N: usize = 8;
+
+#[test]
+fn undo_and_redo_skip_steps_that_no_longer_apply() {
+    let gases = comp_from_config!(GasRegistry, "config/gases.ron");
+    let mut walls = [[false; N]; N];
+    let mut tiles = [[GasMixture::default(); N]; N];
+    let mut stockpile = Stockpile { metal: 10.0 };
+    //Someone else already put a wall on the tile of the newest step
+    walls[2][0] = true;
+    let mut history = EditHistory::default();
+    history.record(vec![wall_edit(1)]);
+    history.undo.push(vec![wall_edit(2)]);
+
+    let mut apply = |step: Vec<Edit>| -> Vec<Edit> {
+        step.into_iter()
+            .rev()
+            .filter_map(|edit| edit_walls(edit, &mut walls, &mut tiles, &mut stockpile, &gases))
+            .collect()
+    };
+    history.undo_step(&mut apply);
+    assert!(history.undo.is_empty());
+    assert_eq!(history.redo.len(), 1);
+
+    history.redo_step(&mut apply);
+    assert_eq!(history.undo.len(), 1);
+    assert!(history.redo.is_empty());
+
+    //Redo with nothing to redo does nothing, undo passes the stale step again and a press with
+    //nothing left leaves both stacks alone
+    history.undo.push(vec![wall_edit(2)]);
+    history.redo_step(&mut apply);
+    history.undo_step(&mut apply);
+    history.undo_step(&mut apply);
+    assert!(history.undo.is_empty());
+    assert_eq!(history.redo.len(), 1);
+    assert!(walls[1][0] && walls[2][0]);
+}
+
+fn total(tiles: &[[GasMixture; N]; N], gas: Gas) -> f64 {
+    tiles.iter().flatten().map(|tile| tile.amount[gas.0]).sum()
+}
+
+#[test]
+fn undo_and_redo_of_a_wall_put_gas_and_metal_back() {
+    let gases = comp_from_config!(GasRegistry, "config/gases.ron");
+    let oxygen = gases.get("Oxygen").unwrap();
+    //Closed room with more gas on the tile that gets walled over, even around it so taking
+    //the gas back out of the neighbours leaves each as it was
+    let mut walls = [[false; N]; N];
+    let mut tiles = [[GasMixture::default(); N]; N];
+    for i in 0..N {
+        for j in 0..N {
+            walls[i][j] = i == 0 || j == 0 || i == N - 1 || j == N - 1;
+            tiles[i][j].temperature = 293.0;
+            if !walls[i][j] {
+                tiles[i][j].amount[oxygen.0] = 100.0;
+            }
+        }
+    }
+    tiles[3][3].amount[oxygen.0] = 400.0;
+    let start = tiles;
+    let mut stockpile = Stockpile { metal: 10.0 };
+    let place = Edit {
+        kind: EditKind::AddWall {
+            tile: (3, 3),
+            material: WallMaterial::default(),
+        },
+        metal: -5.0,
+    };
+
+    let undo = edit_walls(place, &mut walls, &mut tiles, &mut stockpile, &gases).unwrap();
+    assert!(walls[3][3]);
+    assert_eq!(stockpile.metal, 5.0);
+    assert_eq!(tiles[3][3].total_moles(), 0.0);
+    assert!((total(&tiles, oxygen) - total(&start, oxygen)).abs() < 1e-9);
+    //Already walled
+    assert!(edit_walls(place, &mut walls, &mut tiles, &mut stockpile, &gases).is_none());
+    assert_eq!(stockpile.metal, 5.0);
+
+    let redo = edit_walls(undo, &mut walls, &mut tiles, &mut stockpile, &gases).unwrap();
+    assert!(!walls[3][3]);
+    assert_eq!(stockpile.metal, 10.0);
+    for i in 0..N {
+        for j in 0..N {
+            let (now, then) = (&tiles[i][j], &start[i][j]);
+            assert!((now.amount[oxygen.0] - then.amount[oxygen.0]).abs() < 1e-9);
+            assert!((now.temperature - then.temperature).abs() < 1e-9);
+        }
+    }
+
+    edit_walls(redo, &mut walls, &mut tiles, &mut stockpile, &gases).unwrap();
+    assert!(walls[3][3]);
+    assert_eq!(stockpile.metal, 5.0);
+    assert_eq!(tiles[3][3].total_moles(), 0.0);
+}
@@ -0,0 +1,54 @@
+use bevy_space_game::prelude::*;
+
+#[test]
+fn parses_simple_commands() {
+    assert_eq!(ConsoleCommand::parse("help"), Ok(ConsoleCommand::Help));
+    assert_eq!(
+        ConsoleCommand::parse("  status "),
+        Ok(ConsoleCommand::Status)
+    );
+    assert_eq!(ConsoleCommand::parse("rooms"), Ok(ConsoleCommand::Rooms));
+    assert_eq!(ConsoleCommand::parse("exit"), Ok(ConsoleCommand::Exit));
+}
+
+#[test]
+fn parses_vent_commands() {
+    assert_eq!(
+        ConsoleCommand::parse("vent 2"),
+        Ok(ConsoleCommand::Vent {
+            room: 2,
+            target: None
+        })
+    );
+    assert_eq!(
+        ConsoleCommand::parse("vent 0 1.5"),
+        Ok(ConsoleCommand::Vent {
+            room: 0,
+            target: Some(1.5)
+        })
+    );
+    assert!(ConsoleCommand::parse("vent").is_err());
+    assert!(ConsoleCommand::parse("vent one").is_err());
+    assert!(ConsoleCommand::parse("vent 0 -1").is_err());
+    assert!(ConsoleCommand::parse("vent 0 1 2").is_err());
+}
+
+#[test]
+fn parses_door_commands() {
+    assert_eq!(
+        ConsoleCommand::parse("door open 3"),
+        Ok(ConsoleCommand::Door { id: 3, open: true })
+    );
+    assert_eq!(
+        ConsoleCommand::parse("door close 0"),
+        Ok(ConsoleCommand::Door { id: 0, open: false })
+    );
+    assert!(ConsoleCommand::parse("door 3").is_err());
+    assert!(ConsoleCommand::parse("door open").is_err());
+}
+
+#[test]
+fn rejects_unknown_commands() {
+    assert!(ConsoleCommand::parse("").is_err());
+    assert!(ConsoleCommand::parse("reboot").is_err());
+}
@@ -0,0 +1,59 @@
+use bevy_space_game::prelude::*;
+
+const DT: f64 = 1.0 / 60.0;
+
+fn furnace() -> Furnace {
+    comp_from_config!(Furnace, "config/furnace.ron")
+}
+
+fn gases() -> GasRegistry {
+    comp_from_config!(GasRegistry, "config/gases.ron")
+}
+
+#[test]
+fn burning_turns_oxygen_into_carbon_dioxide() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let carbon_dioxide = gases.get("CarbonDioxide").unwrap();
+    let mut furnace = furnace();
+    let mut tile = GasMixture::single_gas(oxygen, 83.0, 293.0);
+    let start_temperature = furnace.temperature;
+    let start_fuel = furnace.fuel;
+
+    for _ in 0..600 {
+        furnace.burn(&mut tile, oxygen, carbon_dioxide, DT);
+    }
+    assert!(furnace.burning);
+    assert!(furnace.fuel < start_fuel);
+    assert!(furnace.temperature > start_temperature);
+    assert!(tile.amount[carbon_dioxide.0] > 0.0);
+    assert!((tile.total_moles() - 83.0).abs() < 1e-9);
+}
+
+#[test]
+fn goes_out_without_oxygen() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let carbon_dioxide = gases.get("CarbonDioxide").unwrap();
+    let mut furnace = furnace();
+    let mut tile = GasMixture::single_gas(oxygen, 0.5, 293.0);
+
+    furnace.burn(&mut tile, oxygen, carbon_dioxide, DT);
+    assert!(!furnace.burning);
+    assert_eq!(tile.amount[oxygen.0], 0.5);
+}
+
+#[test]
+fn goes_out_without_fuel() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let carbon_dioxide = gases.get("CarbonDioxide").unwrap();
+    let mut furnace = Furnace {
+        fuel: 0.0,
+        ..furnace()
+    };
+    let mut tile = GasMixture::single_gas(oxygen, 83.0, 293.0);
+
+    furnace.burn(&mut tile, oxygen, carbon_dioxide, DT);
+    assert!(!furnace.burning);
+}
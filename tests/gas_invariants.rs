@@ -0,0 +1,467 @@
+use bevy::tasks::TaskPool;
+use bevy_space_game::prelude::*;
+
+const N: usize = 20;
+const DT: f64 = 1.0 / 60.0;
+
+type Tiles = Box<[[GasMixture; N]; N]>;
+type Walls = Box<[[bool; N]; N]>;
+
+fn space() -> Tiles {
+    new_boxed_grid(GasMixture {
+        temperature: 2.7,
+        ..Default::default()
+    })
+}
+
+//Walls the border of the rectangle, like create_room
+fn add_room(walls: &mut Walls, x: usize, y: usize, width: usize, height: usize) {
+    for i in x..x + width {
+        for j in y..y + height {
+            if i == x || i == x + width - 1 || j == y || j == y + height - 1 {
+                walls[i][j] = true;
+            }
+        }
+    }
+}
+
+fn gases() -> GasRegistry {
+    comp_from_config!(GasRegistry, "config/gases.ron")
+}
+
+fn step(
+    x0: &Tiles,
+    walls: &Walls,
+    gases: &GasRegistry,
+    solver: GasSolver,
+    pool: &TaskPool,
+) -> Tiles {
+    //Keep heat in so only the moles are under test
+    let thermal = thermal(walls, WallMaterial::Insulated);
+    let mut x = new_boxed_grid(GasMixture::default());
+    diffuse_step(x0, &mut x, walls, &thermal, gases, DT, solver, pool);
+    x
+}
+
+fn thermal(walls: &Walls, material: WallMaterial) -> Box<[[Thermal; N]; N]> {
+    let mut thermal = new_boxed_grid(Thermal::GAS);
+    for i in 0..N {
+        for j in 0..N {
+            if walls[i][j] {
+                thermal[i][j] = material.thermal();
+            }
+        }
+    }
+    thermal
+}
+
+//Every tile, walls included, so gas leaking into a wall shows up as a change
+fn grid_total(tiles: &Tiles, gas: Gas) -> f64 {
+    tiles.iter().flatten().map(|tile| tile.amount[gas.0]).sum()
+}
+
+//Room from (2, 2) to (11, 11) with all the oxygen piled in one corner
+fn corner_room(oxygen: Gas) -> (Tiles, Walls) {
+    let mut tiles = space();
+    let mut walls = new_boxed_grid(false);
+    add_room(&mut walls, 2, 2, 10, 10);
+    for i in 3..11 {
+        for j in 3..11 {
+            tiles[i][j].temperature = 293.0;
+        }
+    }
+    tiles[3][3].amount[oxygen.0] = 1000.0;
+    (tiles, walls)
+}
+
+//Every tile inside corner_room within 1% of each other
+fn settled(tiles: &Tiles, gas: Gas) -> bool {
+    let room = tiles[3..11].iter().flat_map(|row| row[3..11].iter());
+    let (min, max) = room.fold((f64::MAX, f64::MIN), |(min, max), tile| {
+        let amount = tile.amount[gas.0];
+        (min.min(amount), max.max(amount))
+    });
+    (max - min) / max < 0.01
+}
+
+#[test]
+fn moles_are_conserved() {
+    let pool = TaskPool::new();
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    for solver in [GasSolver::GaussSeidel, GasSolver::RedBlack] {
+        let (mut tiles, walls) = corner_room(oxygen);
+        let start = grid_total(&tiles, oxygen);
+        for _ in 0..200 {
+            tiles = step(&tiles, &walls, &gases, solver, &pool);
+        }
+        let end = grid_total(&tiles, oxygen);
+        assert!(
+            ((end - start) / start).abs() < 1e-3,
+            "{:?} went from {} to {} moles",
+            solver,
+            start,
+            end
+        );
+    }
+}
+
+#[test]
+fn amounts_never_go_negative() {
+    let pool = TaskPool::new();
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    for solver in [GasSolver::GaussSeidel, GasSolver::RedBlack] {
+        let (mut tiles, walls) = corner_room(oxygen);
+        tiles[10][10].amount[gases.get("CarbonDioxide").unwrap().0] = 500.0;
+        for _ in 0..200 {
+            tiles = step(&tiles, &walls, &gases, solver, &pool);
+            for tile in tiles.iter().flatten() {
+                assert!(tile.amount.iter().all(|amount| *amount >= 0.0));
+                assert!(tile.temperature >= 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn sealed_rooms_stay_sealed() {
+    let pool = TaskPool::new();
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    for solver in [GasSolver::GaussSeidel, GasSolver::RedBlack] {
+        let (mut tiles, mut walls) = corner_room(oxygen);
+        //Second room sharing the right wall of the first
+        add_room(&mut walls, 11, 2, 6, 10);
+        for _ in 0..200 {
+            tiles = step(&tiles, &walls, &gases, solver, &pool);
+        }
+        for i in 0..N {
+            for j in 0..N {
+                let inside_first = (3..11).contains(&i) && (3..11).contains(&j);
+                if !walls[i][j] && !inside_first {
+                    assert_eq!(
+                        tiles[i][j].amount[oxygen.0], 0.0,
+                        "{:?} leaked into ({}, {})",
+                        solver, i, j
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn room_reaches_equilibrium() {
+    let pool = TaskPool::new();
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    for solver in [GasSolver::GaussSeidel, GasSolver::RedBlack] {
+        let (mut tiles, walls) = corner_room(oxygen);
+        let mut steps = 0;
+        loop {
+            tiles = step(&tiles, &walls, &gases, solver, &pool);
+            steps += 1;
+
+            if settled(&tiles, oxygen) {
+                break;
+            }
+            assert!(steps < 2000, "{:?} did not settle", solver);
+        }
+    }
+}
+
+#[test]
+fn over_pressure_spills_without_losing_moles() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let (mut tiles, walls) = corner_room(oxygen);
+    let start = grid_total(&tiles, oxygen);
+    let max_pressure = 5.0;
+    assert!(tiles[3][3].pressure(TILE_VOLUME) > max_pressure);
+
+    relieve_pressure(&mut tiles, &walls, &new_boxed_grid(max_pressure), &gases);
+
+    let end = grid_total(&tiles, oxygen);
+    assert!(((end - start) / start).abs() < 1e-9);
+    assert!(tiles[3][3].pressure(TILE_VOLUME) <= max_pressure + 1e-9);
+    assert!(tiles[4][3].amount[oxygen.0] > 0.0);
+    assert!(tiles[3][4].amount[oxygen.0] > 0.0);
+    //Never into the walls
+    assert_eq!(tiles[2][3].amount[oxygen.0], 0.0);
+}
+
+#[test]
+fn spilled_gas_stops_at_max_pressure_once_mixed() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let carbon_dioxide = gases.get("CarbonDioxide").unwrap();
+    let max_pressure = 5.0;
+    for (source, neighbour) in [(1000.0, 100.0), (300.0, 1000.0)] {
+        let (mut tiles, walls) = corner_room(oxygen);
+        tiles[3][3].temperature = source;
+        //Neighbours just under the limit so they fill before the source is relieved, a gas
+        //with another heat capacity so sizing by the source temperature alone overshoots
+        for (i, j) in [(4, 3), (3, 4)] {
+            tiles[i][j].temperature = neighbour;
+            tiles[i][j].amount[carbon_dioxide.0] =
+                0.9 * max_pressure * TILE_VOLUME / (IDEAL_GAS_CONST * neighbour);
+        }
+
+        relieve_pressure(&mut tiles, &walls, &new_boxed_grid(max_pressure), &gases);
+
+        assert!(tiles[3][3].pressure(TILE_VOLUME) > max_pressure);
+        for (i, j) in [(4, 3), (3, 4)] {
+            let pressure = tiles[i][j].pressure(TILE_VOLUME);
+            assert!(
+                (pressure - max_pressure).abs() < 1e-9,
+                "{}K into {}K left {} atm",
+                source,
+                neighbour,
+                pressure
+            );
+        }
+    }
+}
+
+#[test]
+fn each_tile_takes_spilled_gas_up_to_its_own_limit() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let (mut tiles, walls) = corner_room(oxygen);
+    let start = grid_total(&tiles, oxygen);
+    let mut max_pressure = new_boxed_grid(5.0);
+    max_pressure[4][3] = 2.0;
+    max_pressure[3][4] = 8.0;
+
+    relieve_pressure(&mut tiles, &walls, &max_pressure, &gases);
+
+    let end = grid_total(&tiles, oxygen);
+    assert!(((end - start) / start).abs() < 1e-9);
+    let pressure = |(i, j): (usize, usize)| tiles[i][j].pressure(TILE_VOLUME);
+    assert!(pressure((3, 3)) >= 5.0 - 1e-9);
+    assert!(pressure((4, 3)) > 0.0 && pressure((4, 3)) <= 2.0 + 1e-9);
+    assert!(pressure((3, 4)) > pressure((4, 3)) && pressure((3, 4)) <= 8.0 + 1e-9);
+}
+
+#[test]
+fn walling_over_gas_pushes_it_out() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let (mut tiles, mut walls) = corner_room(oxygen);
+    let start = grid_total(&tiles, oxygen);
+
+    walls[3][3] = true;
+    wall_over_gas(&mut tiles, &walls, &[(3, 3)], &gases);
+
+    assert!(((grid_total(&tiles, oxygen) - start) / start).abs() < 1e-9);
+    assert_eq!(tiles[3][3].total_moles(), 0.0);
+    assert_eq!(tiles[3][3].temperature, 293.0);
+    assert_eq!(tiles[4][3].amount[oxygen.0], 500.0);
+    assert_eq!(tiles[3][4].amount[oxygen.0], 500.0);
+}
+
+#[test]
+fn gas_goes_through_walls_placed_with_it() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let (mut tiles, mut walls) = corner_room(oxygen);
+    let new_walls = [(3, 3), (4, 3), (3, 4)];
+    for (i, j) in new_walls {
+        walls[i][j] = true;
+    }
+
+    //Never through the room walls around it
+    let mut targets = displacement_targets(&walls, &new_walls, (3, 3));
+    targets.sort();
+    assert_eq!(targets, vec![(3, 5), (4, 4), (5, 3)]);
+    let start = tiles[3][3].total_moles();
+    wall_over_gas(&mut tiles, &walls, &new_walls, &gases);
+    let moved: f64 = targets
+        .iter()
+        .map(|(i, j)| tiles[*i][*j].amount[oxygen.0])
+        .sum();
+    assert!((moved - start).abs() < 1e-9);
+}
+
+#[test]
+fn sealed_pockets_keep_their_gas() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let (mut tiles, mut walls) = corner_room(oxygen);
+    walls[4][3] = true;
+    walls[3][4] = true;
+    walls[3][3] = true;
+
+    assert!(displacement_targets(&walls, &[(3, 3)], (3, 3)).is_empty());
+    wall_over_gas(&mut tiles, &walls, &[(3, 3)], &gases);
+    assert_eq!(tiles[3][3].amount[oxygen.0], 1000.0);
+}
+
+#[test]
+fn reclaimed_gas_keeps_its_heat() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let nitrogen = gases.get("Nitrogen").unwrap();
+    let mut hot = GasMixture::single_gas(oxygen, 10.0, 400.0);
+    hot.amount[nitrogen.0] = 10.0;
+    let cold = GasMixture::single_gas(oxygen, 30.0, 200.0);
+    let mut targets = [hot, cold];
+    let heat = |mixture: &GasMixture| mixture.heat_capacity(&gases) * mixture.temperature;
+    let start: f64 = targets.iter().map(heat).sum();
+
+    let mut tile = GasMixture {
+        temperature: 1000.0,
+        ..Default::default()
+    };
+    let mut wanted = GasMixture::single_gas(oxygen, 20.0, 1000.0);
+    wanted.amount[nitrogen.0] = 5.0;
+    reclaim_gas(&mut tile, &wanted, &mut targets, &gases);
+
+    assert!((tile.amount[oxygen.0] - 20.0).abs() < 1e-9);
+    assert!((tile.amount[nitrogen.0] - 5.0).abs() < 1e-9);
+    assert!(tile.temperature > 200.0 && tile.temperature < 400.0);
+    let end = heat(&tile) + targets.iter().map(heat).sum::<f64>();
+    assert!(((end - start) / start).abs() < 1e-9);
+}
+
+#[test]
+fn walling_and_unwalling_keeps_the_heat() {
+    let gases = gases();
+    let oxygen = gases.get("Oxygen").unwrap();
+    let carbon_dioxide = gases.get("CarbonDioxide").unwrap();
+    let (mut tiles, mut walls) = corner_room(oxygen);
+    //Gases with different heat capacities at different temperatures, mixing by moles would
+    //make or lose heat here
+    tiles[4][3] = GasMixture::single_gas(carbon_dioxide, 200.0, 250.0);
+    tiles[3][3].temperature = 400.0;
+    let heat = |tiles: &Tiles| -> f64 {
+        tiles
+            .iter()
+            .flatten()
+            .map(|tile| tile.heat_capacity(&gases) * tile.temperature)
+            .sum()
+    };
+    let start = heat(&tiles);
+    let gas = tiles[3][3];
+
+    walls[3][3] = true;
+    wall_over_gas(&mut tiles, &walls, &[(3, 3)], &gases);
+    assert!(((heat(&tiles) - start) / start).abs() < 1e-9);
+
+    unwall_gas(&mut tiles, &walls, (3, 3), &gas, &gases);
+    walls[3][3] = false;
+    assert!(((heat(&tiles) - start) / start).abs() < 1e-9);
+}
+
+#[test]
+fn gas_only_stays_over_floors_or_inside_walls() {
+    let oxygen = gases().get("Oxygen").unwrap();
+    let (mut tiles, mut walls) = corner_room(oxygen);
+    let mut floors = new_boxed_grid(false);
+    //Unfloored room sealed by walls, and a floored patch out in the open
+    floors[15][15] = true;
+    for tile in [(15, 15), (16, 15), (3, 3)] {
+        tiles[tile.0][tile.1].amount[oxygen.0] = 10.0;
+    }
+
+    let space = space_mask(&walls, &floors);
+    assert!(!space[3][3]);
+    assert!(!space[15][15]);
+    assert!(space[16][15]);
+    assert!(!space[2][2], "walls are not space");
+
+    vent_to_space(&mut tiles, &space);
+    assert_eq!(tiles[3][3].amount[oxygen.0], 10.0);
+    assert_eq!(tiles[15][15].amount[oxygen.0], 10.0);
+    assert_eq!(tiles[16][15].total_moles(), 0.0);
+    assert_eq!(tiles[16][15].temperature, SPACE_TEMPERATURE);
+
+    //A hole in the wall lets space into the room
+    walls[2][5] = false;
+    let space = space_mask(&walls, &floors);
+    assert!(space[3][3]);
+}
+
+#[test]
+fn light_gases_settle_first() {
+    let pool = TaskPool::new();
+    let gases = gases();
+    let hydrogen = gases.get("Hydrogen").unwrap();
+    let helium = gases.get("Helium3").unwrap();
+    let carbon_dioxide = gases.get("CarbonDioxide").unwrap();
+    assert!(gases.diffusion_rate(hydrogen) > gases.diffusion_rate(carbon_dioxide));
+
+    let (mut tiles, walls) = corner_room(hydrogen);
+    tiles[3][3].amount[helium.0] = 1000.0;
+    tiles[3][3].amount[carbon_dioxide.0] = 1000.0;
+
+    let mut settled_at = [None; 3];
+    for steps in 0..5000 {
+        tiles = step(&tiles, &walls, &gases, GasSolver::GaussSeidel, &pool);
+        for (i, gas) in [hydrogen, helium, carbon_dioxide].iter().enumerate() {
+            if settled_at[i].is_none() && settled(&tiles, *gas) {
+                settled_at[i] = Some(steps);
+            }
+        }
+        if settled_at.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    let [hydrogen, helium, carbon_dioxide] = settled_at.map(Option::unwrap);
+    assert!(hydrogen < helium, "{} {}", hydrogen, helium);
+    assert!(helium < carbon_dioxide, "{} {}", helium, carbon_dioxide);
+}
+
+//Hot room sharing a wall with a cold room, returns the cold room temperature and the total heat
+fn heat_through_wall(material: WallMaterial, steps: usize) -> (f64, f64) {
+    let pool = TaskPool::new();
+    let gases = gases();
+    let mut walls = new_boxed_grid(false);
+    add_room(&mut walls, 2, 2, 6, 6);
+    add_room(&mut walls, 7, 2, 6, 6);
+    let thermal = thermal(&walls, material);
+    let mut tiles = new_boxed_grid(GasMixture {
+        temperature: 200.0,
+        ..Default::default()
+    });
+    for i in 2..7 {
+        for j in 2..8 {
+            tiles[i][j].temperature = 400.0;
+        }
+    }
+
+    for _ in 0..steps {
+        let mut x = new_boxed_grid(GasMixture::default());
+        diffuse_step(
+            &tiles,
+            &mut x,
+            &walls,
+            &thermal,
+            &gases,
+            DT,
+            GasSolver::GaussSeidel,
+            &pool,
+        );
+        tiles = x;
+    }
+
+    let mut heat = 0.0;
+    for i in 0..N {
+        for j in 0..N {
+            heat += thermal[i][j].heat_capacity * tiles[i][j].temperature;
+        }
+    }
+    (tiles[10][5].temperature, heat)
+}
+
+#[test]
+fn walls_conduct_heat_by_material() {
+    let (_, start_heat) = heat_through_wall(WallMaterial::Metal, 0);
+    let (metal, metal_heat) = heat_through_wall(WallMaterial::Metal, 300);
+    let (insulated, _) = heat_through_wall(WallMaterial::Insulated, 300);
+    assert!(metal > insulated, "{} {}", metal, insulated);
+    assert!(insulated > 200.0);
+    assert!(metal < 300.0);
+    assert!(((metal_heat - start_heat) / start_heat).abs() < 1e-3);
+}
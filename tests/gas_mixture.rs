@@ -0,0 +1,27 @@
+use bevy_space_game::prelude::*;
+
+#[test]
+fn describe_lists_present_gases() {
+    let gases: GasRegistry = comp_from_config!(GasRegistry, "config/gases.ron");
+    let oxygen = gases.get("Oxygen").unwrap();
+    let mixture = GasMixture::single_gas(oxygen, 83.0, 293.0);
+
+    let text = mixture.describe(TILE_VOLUME, &gases);
+    assert!(text.starts_with(&format!("{:.2} atm 293K", mixture.pressure(TILE_VOLUME))));
+    assert!(text.contains("Oxygen: 83.0 mol"));
+    assert_eq!(text.lines().count(), 2);
+}
+
+#[test]
+fn toxicity_comes_from_the_toxic_gases() {
+    let gases: GasRegistry = comp_from_config!(GasRegistry, "config/gases.ron");
+    let oxygen = gases.get("Oxygen").unwrap();
+    let carbon_dioxide = gases.get("CarbonDioxide").unwrap();
+    let mut mixture = GasMixture::single_gas(oxygen, 83.0, 293.0);
+    assert_eq!(mixture.toxicity(&gases), 0.0);
+
+    mixture.amount[carbon_dioxide.0] = 10.0;
+    let expected = mixture.get_pressure(carbon_dioxide) * gases.desc(carbon_dioxide).toxicity;
+    assert!(expected > 0.0);
+    assert!((mixture.toxicity(&gases) - expected).abs() < 1e-12);
+}
@@ -0,0 +1,261 @@
+use std::f32::consts::PI;
+
+use bevy_space_game::prelude::*;
+
+//4x2 sprite where only the top left pixel is solid
+fn corner_hitbox(flip_x: bool, flip_y: bool) -> PixelPerfectHitBox {
+    let mut mask = vec![vec![false; 2]; 4];
+    mask[0][0] = true;
+    PixelPerfectHitBox::new(mask, flip_x, flip_y)
+}
+
+fn at(x: f32, y: f32) -> GlobalTransform {
+    GlobalTransform::from_xyz(x, y, 100.0)
+}
+
+#[test]
+fn hits_untransformed_sprite() {
+    let hit_box = corner_hitbox(false, false);
+    let transform = at(100.0, 50.0);
+
+    assert_eq!(
+        hit_box.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(98.5, 50.5)
+        ),
+        Some((0, 0))
+    );
+    assert_eq!(
+        hit_box.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(101.5, 50.5)
+        ),
+        None
+    );
+    assert_eq!(
+        hit_box.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(98.5, 49.5)
+        ),
+        None
+    );
+    assert_eq!(
+        hit_box.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(90.0, 50.5)
+        ),
+        None
+    );
+}
+
+#[test]
+fn hits_rotated_sprite() {
+    let hit_box = corner_hitbox(false, false);
+    //A quarter turn counter clockwise moves the top left corner to the bottom left
+    let transform = at(0.0, 0.0).with_rotation(Quat::from_rotation_z(PI / 2.0));
+
+    assert_eq!(
+        hit_box.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(-0.5, -1.5)
+        ),
+        Some((0, 0))
+    );
+    assert_eq!(
+        hit_box.pixel_at(&transform, TileOrientation::default(), Vec2::new(-1.5, 0.5)),
+        None
+    );
+    //Below the unrotated sprite but still on the rotated one
+    assert_eq!(
+        hit_box.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(-0.5, -1.9)
+        ),
+        Some((0, 0))
+    );
+    assert_eq!(
+        hit_box.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(-1.5, -0.5)
+        ),
+        None
+    );
+}
+
+#[test]
+fn hits_scaled_sprite() {
+    let hit_box = corner_hitbox(false, false);
+    let transform = at(0.0, 0.0).with_scale(Vec3::splat(2.0));
+
+    assert_eq!(
+        hit_box.pixel_at(&transform, TileOrientation::default(), Vec2::new(-3.5, 1.5)),
+        Some((0, 0))
+    );
+    assert_eq!(
+        hit_box.pixel_at(&transform, TileOrientation::default(), Vec2::new(-1.5, 1.5)),
+        None
+    );
+    assert_eq!(
+        hit_box.pixel_at(&transform, TileOrientation::default(), Vec2::new(-4.5, 1.5)),
+        None
+    );
+}
+
+#[test]
+fn hits_flipped_sprite() {
+    let transform = at(0.0, 0.0);
+
+    //Flipped in x the solid pixel is drawn top right
+    let flipped_x = corner_hitbox(true, false);
+    assert_eq!(
+        flipped_x.pixel_at(&transform, TileOrientation::default(), Vec2::new(1.5, 0.5)),
+        Some((0, 0))
+    );
+    assert_eq!(
+        flipped_x.pixel_at(&transform, TileOrientation::default(), Vec2::new(-1.5, 0.5)),
+        None
+    );
+
+    //Flipped in y it is drawn bottom left
+    let flipped_y = corner_hitbox(false, true);
+    assert_eq!(
+        flipped_y.pixel_at(
+            &transform,
+            TileOrientation::default(),
+            Vec2::new(-1.5, -0.5)
+        ),
+        Some((0, 0))
+    );
+    assert_eq!(
+        flipped_y.pixel_at(&transform, TileOrientation::default(), Vec2::new(-1.5, 0.5)),
+        None
+    );
+
+    let flipped_both = corner_hitbox(true, true);
+    assert_eq!(
+        flipped_both.pixel_at(&transform, TileOrientation::default(), Vec2::new(1.5, -0.5)),
+        Some((0, 0))
+    );
+}
+
+#[test]
+fn tile_orientation_flips_on_top_of_the_sprite() {
+    let transform = at(0.0, 0.0);
+    let flip_x = TileOrientation {
+        flip_x: true,
+        flip_y: false,
+    };
+
+    //Flipped by the tile alone the solid pixel is drawn top right
+    let hit_box = corner_hitbox(false, false);
+    assert_eq!(
+        hit_box.pixel_at(&transform, flip_x, Vec2::new(1.5, 0.5)),
+        Some((0, 0))
+    );
+    assert!(hit_box.overlaps(&transform, flip_x, Vec2::new(1.0, 0.0), Vec2::new(2.0, 1.0)));
+
+    //A tile flip undoes the same flip in the sprite
+    let flipped = corner_hitbox(true, false);
+    assert_eq!(flipped.flips(flip_x), (false, false));
+    assert_eq!(
+        flipped.pixel_at(&transform, flip_x, Vec2::new(-1.5, 0.5)),
+        Some((0, 0))
+    );
+    assert!(!flipped.overlaps(&transform, flip_x, Vec2::new(1.0, 0.0), Vec2::new(2.0, 1.0)));
+}
+
+#[test]
+fn outlines_around_solid_pixels() {
+    let outline = corner_hitbox(false, false).outline();
+
+    //One pixel border on every side
+    assert_eq!(outline.len(), 6);
+    assert_eq!(outline[0].len(), 4);
+    //The solid pixel sits at (1, 1) with the border, its four neighbours are outlined
+    assert!(!outline[1][1]);
+    assert!(outline[0][1] && outline[2][1] && outline[1][0] && outline[1][2]);
+    //Diagonals and anything further away are not
+    assert!(!outline[0][0] && !outline[2][2] && !outline[3][1]);
+    let count: usize = outline
+        .iter()
+        .map(|column| column.iter().filter(|pixel| **pixel).count())
+        .sum();
+    assert_eq!(count, 4);
+}
+
+#[test]
+fn box_overlaps_solid_pixels_only() {
+    let hit_box = corner_hitbox(false, false);
+    let transform = at(0.0, 0.0);
+
+    //The solid pixel's center is at (-1.5, 0.5)
+    assert!(hit_box.overlaps(
+        &transform,
+        TileOrientation::default(),
+        Vec2::new(-2.0, 0.0),
+        Vec2::new(-1.0, 1.0)
+    ));
+    assert!(hit_box.overlaps(
+        &transform,
+        TileOrientation::default(),
+        Vec2::new(-10.0, -10.0),
+        Vec2::new(10.0, 10.0)
+    ));
+    //Covers the sprite's empty pixels but not the solid one
+    assert!(!hit_box.overlaps(
+        &transform,
+        TileOrientation::default(),
+        Vec2::new(-1.0, -1.0),
+        Vec2::new(2.0, 1.0)
+    ));
+
+    //Follows rotation and flips like pixel_at
+    let rotated = at(0.0, 0.0).with_rotation(Quat::from_rotation_z(PI / 2.0));
+    assert!(hit_box.overlaps(
+        &rotated,
+        TileOrientation::default(),
+        Vec2::new(-1.0, -2.0),
+        Vec2::new(0.0, -1.0)
+    ));
+    let flipped = corner_hitbox(true, false);
+    assert!(flipped.overlaps(
+        &transform,
+        TileOrientation::default(),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(2.0, 1.0)
+    ));
+    assert!(!flipped.overlaps(
+        &transform,
+        TileOrientation::default(),
+        Vec2::new(-2.0, 0.0),
+        Vec2::new(-1.0, 1.0)
+    ));
+}
+
+#[test]
+fn orientation_rotates_clockwise() {
+    let mut facing = Orientation::Up;
+    let mut seen = Vec::new();
+    for _ in 0..4 {
+        facing = facing.rotated();
+        seen.push(facing);
+    }
+    assert_eq!(
+        seen,
+        [
+            Orientation::Right,
+            Orientation::Down,
+            Orientation::Left,
+            Orientation::Up
+        ]
+    );
+    assert_eq!(Orientation::Right.angle(), 0.0);
+    assert!((Orientation::Up.angle() - PI / 2.0).abs() < 1e-6);
+}
@@ -0,0 +1,73 @@
+use bevy_space_game::prelude::*;
+
+const DT: f64 = 1.0 / 60.0;
+const PIPE_VOLUME: f64 = 0.2;
+
+fn gas(gases: &GasRegistry, name: &str) -> Gas {
+    gases.get(name).unwrap()
+}
+
+fn gases() -> GasRegistry {
+    comp_from_config!(GasRegistry, "config/gases.ron")
+}
+
+#[test]
+fn flow_evens_out_pressure_without_losing_moles() {
+    let gases = gases();
+    let oxygen = gas(&gases, "Oxygen");
+    let mut full = GasMixture::single_gas(oxygen, 20.0, 293.0);
+    let mut empty = GasMixture::single_gas(oxygen, 0.0, 293.0);
+
+    for _ in 0..600 {
+        pipe_flow(
+            &mut full,
+            PIPE_VOLUME,
+            &mut empty,
+            PIPE_VOLUME,
+            50.0,
+            DT,
+            &gases,
+        );
+        assert!(full.pressure(PIPE_VOLUME) >= empty.pressure(PIPE_VOLUME) - 1e-9);
+    }
+    assert!((full.total_moles() + empty.total_moles() - 20.0).abs() < 1e-9);
+    assert!((full.pressure(PIPE_VOLUME) - empty.pressure(PIPE_VOLUME)).abs() < 1e-6);
+}
+
+#[test]
+fn flow_runs_from_high_to_low_pressure() {
+    let gases = gases();
+    let oxygen = gas(&gases, "Oxygen");
+    let nitrogen = gas(&gases, "Nitrogen");
+    let mut low = GasMixture::single_gas(oxygen, 1.0, 293.0);
+    let mut high = GasMixture::single_gas(nitrogen, 10.0, 293.0);
+
+    pipe_flow(
+        &mut low,
+        PIPE_VOLUME,
+        &mut high,
+        PIPE_VOLUME,
+        50.0,
+        DT,
+        &gases,
+    );
+    assert!(low.amount[nitrogen.0] > 0.0);
+    assert!(high.amount[oxygen.0] == 0.0);
+    assert!(high.total_moles() < 10.0);
+}
+
+#[test]
+fn pumps_push_against_pressure_up_to_their_limit() {
+    let gases = gases();
+    let oxygen = gas(&gases, "Oxygen");
+    let mut from = GasMixture::single_gas(oxygen, 50.0, 293.0);
+    let mut to = GasMixture::single_gas(oxygen, 50.0, 293.0);
+
+    for _ in 0..6000 {
+        pump_gas(&mut from, &mut to, PIPE_VOLUME, 5.0 * DT, 8.0, &gases);
+        assert!(to.pressure(PIPE_VOLUME) <= 8.0 + 1e-9);
+    }
+    assert!(to.pressure(PIPE_VOLUME) > from.pressure(PIPE_VOLUME));
+    assert!((to.pressure(PIPE_VOLUME) - 8.0).abs() < 1e-6);
+    assert!((from.total_moles() + to.total_moles() - 100.0).abs() < 1e-9);
+}
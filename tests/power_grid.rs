@@ -0,0 +1,100 @@
+use bevy_space_game::prelude::*;
+
+const DT: f64 = 1.0 / 60.0;
+
+fn battery(charge: f64) -> Battery {
+    Battery {
+        capacity: 1000.0,
+        charge,
+        max_rate: 600.0,
+    }
+}
+
+#[test]
+fn surplus_charges_batteries() {
+    let mut batteries = [battery(0.0)];
+    let satisfaction = balance_grid(1000.0, 400.0, &mut batteries, DT);
+    assert_eq!(satisfaction, 1.0);
+    assert!((batteries[0].charge - 600.0 * DT).abs() < 1e-9);
+}
+
+#[test]
+fn batteries_stop_at_capacity() {
+    let mut batteries = [battery(1000.0 - 1.0)];
+    balance_grid(1000.0, 0.0, &mut batteries, DT);
+    assert!((batteries[0].charge - 1000.0).abs() < 1e-9);
+}
+
+#[test]
+fn batteries_cover_shortfalls() {
+    let mut batteries = [battery(500.0)];
+    let satisfaction = balance_grid(200.0, 700.0, &mut batteries, DT);
+    assert_eq!(satisfaction, 1.0);
+    assert!((batteries[0].charge - (500.0 - 500.0 * DT)).abs() < 1e-9);
+}
+
+#[test]
+fn brownout_shares_what_is_left() {
+    //Battery can only give max_rate so a quarter of the demand goes unmet
+    let mut batteries = [battery(500.0)];
+    let satisfaction = balance_grid(600.0, 1600.0, &mut batteries, DT);
+    assert!((satisfaction - 0.75).abs() < 1e-9);
+
+    let mut flat = [battery(0.0)];
+    let satisfaction = balance_grid(300.0, 1200.0, &mut flat, DT);
+    assert!((satisfaction - 0.25).abs() < 1e-9);
+    assert_eq!(flat[0].charge, 0.0);
+}
+
+const N: usize = 8;
+
+#[test]
+fn connected_wires_share_a_label() {
+    let mut wires = [[false; N]; N];
+    //An L that turns a corner is still one grid
+    for row in wires[1..6].iter_mut() {
+        row[2] = true;
+    }
+    for wire in wires[5][3..7].iter_mut() {
+        *wire = true;
+    }
+    let (labels, count) = power_grid_labels(&wires);
+    assert_eq!(count, 1);
+    assert_eq!(labels[1][2], Some(0));
+    assert_eq!(labels[5][6], Some(0));
+}
+
+#[test]
+fn a_gap_splits_the_grid() {
+    let mut wires = [[false; N]; N];
+    for (i, row) in wires.iter_mut().enumerate() {
+        row[4] = i != 3;
+    }
+    //Touching only at a corner does not connect
+    wires[2][5] = true;
+    wires[3][6] = true;
+    let (labels, count) = power_grid_labels(&wires);
+    assert_eq!(count, 3);
+    assert_eq!(labels[0][4], labels[2][4]);
+    assert_eq!(labels[2][5], labels[2][4]);
+    assert_ne!(labels[2][4], labels[4][4]);
+    assert_eq!(labels[4][4], labels[N - 1][4]);
+    assert_ne!(labels[3][6], labels[2][5]);
+}
+
+#[test]
+fn tiles_off_the_wires_get_no_grid() {
+    let mut wires = [[false; N]; N];
+    wires[2][2] = true;
+    wires[2][3] = true;
+    let (labels, count) = power_grid_labels(&wires);
+    assert_eq!(count, 1);
+    let unlabelled = labels
+        .iter()
+        .flatten()
+        .filter(|label| label.is_none())
+        .count();
+    assert_eq!(unlabelled, N * N - 2);
+    //A machine next to the wire but not on it
+    assert_eq!(labels[3][2], None);
+}
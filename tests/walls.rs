@@ -0,0 +1,173 @@
+use bevy_space_game::prelude::*;
+
+fn empty_grid() -> [[Option<Entity>; GRID_SIZE]; GRID_SIZE] {
+    [[None; GRID_SIZE]; GRID_SIZE]
+}
+
+#[test]
+fn matching_grid_needs_no_repairs() {
+    let mut grid = empty_grid();
+    let wall = Entity::from_raw(1);
+    grid[3][4] = Some(wall);
+    assert!(wall_repairs(&grid, &[(wall, Some((3, 4)))]).is_empty());
+}
+
+#[test]
+fn mismatches_are_repaired() {
+    let mut grid = empty_grid();
+    let missing = Entity::from_raw(1);
+    let unknown = Entity::from_raw(2);
+    let kept = Entity::from_raw(3);
+    let duplicate = Entity::from_raw(4);
+    let lost = Entity::from_raw(5);
+    grid[0][0] = Some(missing);
+    grid[5][5] = Some(kept);
+
+    let walls = [
+        (unknown, Some((1, 1))),
+        (kept, Some((5, 5))),
+        (duplicate, Some((5, 5))),
+        (lost, None),
+    ];
+    assert_eq!(
+        wall_repairs(&grid, &walls),
+        vec![
+            WallRepair::Clear((0, 0)),
+            WallRepair::Insert((1, 1), unknown),
+            WallRepair::Despawn(duplicate),
+            WallRepair::Despawn(lost),
+        ]
+    );
+}
+
+#[test]
+fn wall_moved_to_another_tile_follows() {
+    let mut grid = empty_grid();
+    let wall = Entity::from_raw(1);
+    grid[2][2] = Some(wall);
+    assert_eq!(
+        wall_repairs(&grid, &[(wall, Some((2, 3)))]),
+        vec![WallRepair::Clear((2, 2)), WallRepair::Insert((2, 3), wall)]
+    );
+}
+
+fn rules() -> AutotileRules {
+    comp_from_config!(AutotileRules, "assets/graphics_desc.ron")
+}
+
+fn filled(tiles: &[(usize, usize)]) -> [[Option<Entity>; GRID_SIZE]; GRID_SIZE] {
+    let mut grid = empty_grid();
+    for (x, y) in tiles {
+        grid[*x][*y] = Some(Entity::from_raw(1));
+    }
+    grid
+}
+
+#[test]
+fn corners_need_both_sides() {
+    let grid = filled(&[(5, 5), (6, 6), (6, 5)]);
+    //Right and up right, but not up
+    assert_eq!(autotile_mask(&grid, 5, 5), 0b0000_0100);
+
+    let grid = filled(&[(5, 5), (6, 6), (6, 5), (5, 6)]);
+    assert_eq!(autotile_mask(&grid, 5, 5), 0b0000_0111);
+    //Edges of the grid count as empty
+    assert_eq!(autotile_mask(&filled(&[(0, 0)]), 0, 0), 0);
+}
+
+//Sides the unflipped sprite connects to
+fn sprite_sides(connection: WallConnection) -> u8 {
+    match connection {
+        WallConnection::None => 0,
+        WallConnection::One => 4,
+        WallConnection::OneVertical => 1,
+        WallConnection::Corner => 4 | 16,
+        WallConnection::Cross => 4 | 64,
+        WallConnection::CrossVertical => 1 | 16,
+        WallConnection::T | WallConnection::Edge => 4 | 16 | 64,
+        WallConnection::TVertical | WallConnection::EdgeVertical => 1 | 4 | 16,
+        WallConnection::All | WallConnection::Fill => AutotileRules::SIDES,
+    }
+}
+
+//Sides the sprite connects to once the rule's flips are applied
+fn drawn_sides(rule: AutotileRule) -> u8 {
+    let mut sides = sprite_sides(rule.connection);
+    let swap = |sides: u8, a: u8, b: u8| {
+        let rest = sides & !(a | b);
+        rest | if sides & a != 0 { b } else { 0 } | if sides & b != 0 { a } else { 0 }
+    };
+    if rule.orientation.flip_x {
+        sides = swap(sides, 4, 64);
+    }
+    if rule.orientation.flip_y {
+        sides = swap(sides, 1, 16);
+    }
+    sides
+}
+
+#[test]
+fn every_side_mask_has_a_rule() {
+    let rules = rules();
+    for mask in 0..=u8::MAX {
+        if mask & !AutotileRules::SIDES != 0 {
+            continue;
+        }
+        assert_eq!(drawn_sides(rules.rule(mask)), mask, "mask {:#010b}", mask);
+        assert_eq!(
+            drawn_sides(rules.floor_rule(mask)),
+            mask,
+            "mask {:#010b}",
+            mask
+        );
+    }
+}
+
+#[test]
+fn every_blob_rule_connects_its_sides() {
+    let rules = rules();
+    for mask in 0..=u8::MAX {
+        assert_eq!(
+            drawn_sides(rules.rule(mask)),
+            mask & AutotileRules::SIDES,
+            "mask {:#010b}",
+            mask
+        );
+    }
+}
+
+#[test]
+fn blocks_use_blob_tiles() {
+    let rules = rules();
+    let block: Vec<(usize, usize)> = (4..=6).flat_map(|x| (4..=6).map(move |y| (x, y))).collect();
+    let grid = filled(&block);
+    assert_eq!(autotile_mask(&grid, 5, 5), u8::MAX);
+    assert_eq!(rules.rule(u8::MAX).connection, WallConnection::Fill);
+    //Top middle is open above, bottom middle is the same tile flipped
+    let top = rules.rule(autotile_mask(&grid, 5, 6));
+    let bottom = rules.rule(autotile_mask(&grid, 5, 4));
+    assert_eq!(top.connection, WallConnection::Edge);
+    assert_eq!(bottom.connection, WallConnection::Edge);
+    assert!(!top.orientation.flip_y && bottom.orientation.flip_y);
+    //Left and right middles
+    let left = rules.rule(autotile_mask(&grid, 4, 5));
+    let right = rules.rule(autotile_mask(&grid, 6, 5));
+    assert_eq!(left.connection, WallConnection::EdgeVertical);
+    assert_eq!(right.connection, WallConnection::EdgeVertical);
+    assert!(!left.orientation.flip_x && right.orientation.flip_x);
+    //Pipes and wires only look at the sides
+    assert_eq!(
+        line_autotile(&rules, &grid, 5, 5).connection,
+        WallConnection::All
+    );
+}
+
+#[test]
+fn blob_masks_fall_back_to_their_sides() {
+    let rules = rules();
+    let grid = filled(&[(5, 5), (6, 5), (5, 4), (6, 4)]);
+    let mask = autotile_mask(&grid, 5, 5);
+    assert_eq!(mask, 0b0001_1100);
+    assert_eq!(rules.rule(mask), rules.rule(mask & AutotileRules::SIDES));
+    assert_eq!(rules.rule(mask).connection, WallConnection::Corner);
+}